@@ -0,0 +1,78 @@
+// Times parsing of generated inputs of a few megabytes, run with 'cargo bench'.
+// Any arguments filter the benchmarks by name.
+
+use std::convert::TryFrom;
+use std::env;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+use mold::{ContentTree, Events, ReadEvents, SyntaxTree};
+
+const SIZE: usize = 4 * 1024 * 1024;
+
+// Repeats the piece until the input is at least SIZE bytes.
+fn repeat(piece: &str) -> String {
+    piece.repeat(SIZE / piece.len() + 1)
+}
+
+fn inputs() -> Vec<(&'static str, String)> {
+    vec![
+        ("text", repeat("<p>Lorem ipsum dolor sit amet, consectetur adipiscing elit.</p>\n")),
+        ("unicode text", repeat("Räksmörgås på ängen, 日本語のテキスト, и немного кириллицы.\n")),
+        ("tags", repeat("<li><<page.title>></li><<if a<<<<b>>>>else<<c>>if>>\n")),
+        ("nested", {
+            let open = "<<quote<<x".repeat(64);
+            let close = ">>quote>>".repeat(64);
+            repeat(&format!("{}{}\n", open, close))
+        }),
+        ("single signs", repeat("a < b > c <p>d</p> e<f>g\n")),
+    ]
+}
+
+// Runs the function for at least a second and returns the average time of a run.
+fn time<F: FnMut()>(mut f: F) -> Duration {
+    let start = Instant::now();
+    let mut runs = 0;
+
+    while start.elapsed() < Duration::from_secs(1) {
+        f();
+        runs += 1;
+    }
+
+    start.elapsed() / runs
+}
+
+fn main() {
+    // Arguments added by cargo, e.g. '--bench', aren't filters.
+    let filters: Vec<String> = env::args().skip(1).filter(|a| !a.starts_with("--")).collect();
+
+    for (name, input) in inputs() {
+        if !filters.is_empty() && !filters.iter().any(|f| name.contains(f.as_str())) {
+            continue;
+        }
+
+        let mb = input.len() as f64 / (1024.0 * 1024.0);
+
+        let events = time(|| {
+            black_box(Events::new(black_box(&input)).count());
+        });
+
+        let read = time(|| {
+            black_box(ReadEvents::new(black_box(input.as_bytes())).count());
+        });
+
+        let lossless = time(|| {
+            black_box(SyntaxTree::parse(black_box(&input)));
+        });
+
+        let tree = time(|| {
+            black_box(ContentTree::try_from(black_box(input.as_str())).ok());
+        });
+
+        println!("{} ({:.1} MB)", name, mb);
+
+        for (what, t) in &[("events", events), ("read events", read), ("syntax tree", lossless), ("content tree", tree)] {
+            println!("  {:<14} {:>8.2?} {:>8.1} MB/s", what, t, mb / t.as_secs_f64());
+        }
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashMap;
+
+use crate::content_tree::ContentTree;
+use crate::content_tree::Node;
+use crate::content_tree::Tag;
+use crate::context::Value;
+use crate::error::RenderError;
+use crate::filters;
+
+// What the name of a tag which isn't control flow refers to.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TagKind {
+    Module,
+    Custom,
+    // A variable or a block
+    Other,
+}
+
+// A content tree compiled into a flat list of instructions, so that a template
+// can be rendered for many pages without walking or cloning its tree.
+//
+// The content of a tag follows its instruction, and the instruction tells how
+// many instructions it takes up, so that it can be skipped as a whole.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program<'a> {
+    pub code: Vec<Instr<'a>>,
+    // The blocks which the content extending the program fills in:
+    // the tags at the top, by name. Only the last tag of a name is filled in, like when flattening.
+    slots: HashMap<&'a str, usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instr<'a> {
+    // Text which is written as it is
+    Text(&'a str),
+    // A tag which is neither a module nor control flow: a variable if it has no content,
+    // passed through its filters if it has any. Otherwise the content is used, see 'Renderer'.
+    Tag {
+        name: &'a str,
+        args: &'a str,
+        // The filters if the arguments start with a pipe, or None if they can't be parsed
+        filters: Option<Vec<(&'a str, Vec<Value>)>>,
+        len: usize,
+    },
+    // A module, followed by the content to fill in its '<<content>>'
+    Module { name: &'a str, len: usize },
+    // A tag implemented in Rust, which gets the tag as it is written, see 'Tags'
+    Custom(Tag<'a>),
+    // A loop, followed by its content and then the content of its 'else'
+    For { item: &'a str, collection: &'a str, len: usize, otherwise: usize },
+    // An 'if', followed by its content and then its branches
+    If { condition: &'a str, len: usize, branches: usize },
+    // A branch of an 'if', followed by its content
+    Branch { name: &'a str, condition: &'a str, len: usize },
+    // A tag which fails whenever it is rendered, e.g. a loop without a collection
+    Fail { name: &'a str, error: RenderError },
+}
+
+impl<'a> Instr<'a> {
+    // The name of the tag, if the instruction is one.
+    pub fn name(&self) -> Option<&'a str> {
+        match self {
+            Instr::Text(_) => None,
+            Instr::Tag { name, .. } |
+            Instr::Module { name, .. } |
+            Instr::Branch { name, .. } |
+            Instr::Fail { name, .. } => Some(name),
+            Instr::Custom(tag) => Some(tag.name),
+            Instr::For { .. } => Some("for"),
+            Instr::If { .. } => Some("if"),
+        }
+    }
+
+    // How many instructions the content of the tag takes up.
+    pub fn content_len(&self) -> usize {
+        match self {
+            Instr::Text(_) | Instr::Custom(_) | Instr::Fail { .. } => 0,
+            Instr::Tag { len, .. } |
+            Instr::Module { len, .. } |
+            Instr::For { len, .. } |
+            Instr::If { len, .. } |
+            Instr::Branch { len, .. } => *len,
+        }
+    }
+
+    // How many instructions follow which belong to the tag, including the 'else' or branches.
+    pub fn size(&self) -> usize {
+        match self {
+            Instr::For { len, otherwise, .. } => len + otherwise,
+            Instr::If { len, branches, .. } => len + branches,
+            _ => self.content_len(),
+        }
+    }
+}
+
+impl<'a> Program<'a> {
+    // Compiles a content tree, where 'kind' tells what the names of tags refer to.
+    pub fn compile<F: Fn(&str) -> TagKind>(tree: &ContentTree<'a>, kind: F) -> Program<'a> {
+        let mut code = Vec::new();
+
+        compile_content(&mut code, tree, &kind);
+
+        let mut slots = HashMap::new();
+
+        let mut i = 0;
+        while i < code.len() {
+            if let Some(name) = code[i].name() {
+                slots.insert(name, i);
+            }
+
+            i += 1 + code[i].size();
+        }
+
+        Program {
+            code,
+            slots,
+        }
+    }
+
+    // Whether the instruction at the index is a block which content extending the program fills in.
+    pub fn is_slot(&self, index: usize, name: &str) -> bool {
+        self.slots.get(name) == Some(&index)
+    }
+
+    // The contents of the tags at the top, e.g. the blocks of a page filling in its template.
+    pub fn blocks(&self) -> Vec<(&'a str, &[Instr<'a>])> {
+        let mut blocks = Vec::new();
+
+        let mut i = 0;
+        while i < self.code.len() {
+            let instr = &self.code[i];
+
+            if let Some(name) = instr.name() {
+                blocks.push((name, &self.code[i + 1..i + 1 + instr.content_len()]));
+            }
+
+            i += 1 + instr.size();
+        }
+
+        blocks
+    }
+}
+
+fn compile_content<'a>(code: &mut Vec<Instr<'a>>, tree: &ContentTree<'a>, kind: &dyn Fn(&str) -> TagKind) {
+    for node in &tree.children {
+        match node {
+            Node::Text(text) => code.push(Instr::Text(text)),
+            Node::Tag(tag) => compile_tag(code, tag, kind),
+        }
+    }
+}
+
+// Tags are checked in the order described at 'Renderer'.
+fn compile_tag<'a>(code: &mut Vec<Instr<'a>>, tag: &Tag<'a>, kind: &dyn Fn(&str) -> TagKind) {
+    match tag.name {
+        "for" => return compile_for(code, tag, kind),
+        "if" => return compile_if(code, tag, kind),
+        _ => (),
+    }
+
+    let tag_kind = kind(tag.name);
+
+    // Custom tags may have branches of their own.
+    if tag_kind == TagKind::Custom {
+        code.push(Instr::Custom(tag.clone()));
+        return;
+    }
+
+    if let Some(branch) = tag.branches.first() {
+        return fail(code, tag, RenderError::UnexpectedBranch(branch.name.to_owned()));
+    }
+
+    let start = code.len();
+
+    if tag_kind == TagKind::Module {
+        code.push(Instr::Module { name: tag.name, len: 0 });
+    } else {
+        let filters = match tag.args.starts_with('|') {
+            true => filters::parse_pipeline(tag.args),
+            false => None,
+        };
+
+        code.push(Instr::Tag { name: tag.name, args: tag.args, filters, len: 0 });
+    }
+
+    compile_content(code, &tag.content, kind);
+
+    let n = code.len() - start - 1;
+
+    match &mut code[start] {
+        Instr::Module { len, .. } | Instr::Tag { len, .. } => *len = n,
+        _ => unreachable!(),
+    }
+}
+
+// <<for item in collection<<...>>else<<...>>for>>
+fn compile_for<'a>(code: &mut Vec<Instr<'a>>, tag: &Tag<'a>, kind: &dyn Fn(&str) -> TagKind) {
+    let args: Vec<&str> = tag.args.split_whitespace().collect();
+
+    let (item, collection) = match args.as_slice() {
+        [item, "in", collection] => (*item, *collection),
+        _ => return fail(code, tag, RenderError::InvalidLoop(tag.args.to_owned())),
+    };
+
+    let mut otherwise = None;
+    for branch in &tag.branches {
+        if branch.name != "else" || otherwise.is_some() {
+            return fail(code, tag, RenderError::UnexpectedBranch(branch.name.to_owned()));
+        }
+
+        otherwise = Some(&branch.content);
+    }
+
+    let start = code.len();
+
+    code.push(Instr::For { item, collection, len: 0, otherwise: 0 });
+
+    compile_content(code, &tag.content, kind);
+
+    let content = code.len() - start - 1;
+
+    if let Some(otherwise) = otherwise {
+        compile_content(code, otherwise, kind);
+    }
+
+    let rest = code.len() - start - 1 - content;
+
+    if let Instr::For { len, otherwise, .. } = &mut code[start] {
+        *len = content;
+        *otherwise = rest;
+    }
+}
+
+// <<if cond<<...>>elif cond<<...>>else<<...>>if>>
+// The branches are checked when they are rendered, since they may never be.
+fn compile_if<'a>(code: &mut Vec<Instr<'a>>, tag: &Tag<'a>, kind: &dyn Fn(&str) -> TagKind) {
+    let start = code.len();
+
+    code.push(Instr::If { condition: tag.args, len: 0, branches: 0 });
+
+    compile_content(code, &tag.content, kind);
+
+    let content = code.len() - start - 1;
+
+    for branch in &tag.branches {
+        let b = code.len();
+
+        code.push(Instr::Branch { name: branch.name, condition: branch.args, len: 0 });
+
+        compile_content(code, &branch.content, kind);
+
+        let n = code.len() - b - 1;
+
+        if let Instr::Branch { len, .. } = &mut code[b] {
+            *len = n;
+        }
+    }
+
+    let rest = code.len() - start - 1 - content;
+
+    if let Instr::If { len, branches, .. } = &mut code[start] {
+        *len = content;
+        *branches = rest;
+    }
+}
+
+fn fail<'a>(code: &mut Vec<Instr<'a>>, tag: &Tag<'a>, error: RenderError) {
+    code.push(Instr::Fail { name: tag.name, error });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    fn compile(input: &str) -> Program<'_> {
+        Program::compile(&ContentTree::try_from(input).unwrap(), |name| match name {
+            "quote" => TagKind::Module,
+            "now" => TagKind::Custom,
+            _ => TagKind::Other,
+        })
+    }
+
+    #[test]
+    fn contents_follow_their_tags() {
+        let program = compile("a<<if b<<<<quote<<c>>quote>>>>else<<d>>if>><<e>>");
+
+        assert_eq!(program.code, vec![
+            Instr::Text("a"),
+            Instr::If { condition: "b", len: 2, branches: 2 },
+            Instr::Module { name: "quote", len: 1 },
+            Instr::Text("c"),
+            Instr::Branch { name: "else", condition: "", len: 1 },
+            Instr::Text("d"),
+            Instr::Tag { name: "e", args: "", filters: None, len: 0 },
+        ]);
+    }
+
+    #[test]
+    fn blocks_and_slots() {
+        let program = compile("<<title<<x>>title>>\n<<content>><<title<<y>>title>>");
+
+        let blocks: Vec<(&str, usize)> = program.blocks().iter().map(|(name, code)| (*name, code.len())).collect();
+        assert_eq!(blocks, vec![("title", 1), ("content", 0), ("title", 1)]);
+
+        // Only the last of two blocks with the same name is filled in.
+        assert!(!program.is_slot(0, "title"));
+        assert!(program.is_slot(3, "content"));
+        assert!(program.is_slot(4, "title"));
+    }
+
+    #[test]
+    fn invalid_tags_fail() {
+        let program = compile("<<for posts<<x>>for>><<foo<<x>>else<<y>>foo>><<for p in posts<<x>>elif<<y>>for>>");
+
+        assert_eq!(program.code, vec![
+            Instr::Fail { name: "for", error: RenderError::InvalidLoop("posts".to_owned()) },
+            Instr::Fail { name: "foo", error: RenderError::UnexpectedBranch("else".to_owned()) },
+            Instr::Fail { name: "for", error: RenderError::UnexpectedBranch("elif".to_owned()) },
+        ]);
+    }
+
+    #[test]
+    fn custom_tags_keep_their_content() {
+        let program = compile("<<now<<<<x>>>>else<<y>>now>><<z>>");
+
+        assert_eq!(program.code.len(), 2);
+        assert!(matches!(&program.code[0], Instr::Custom(tag) if tag.name == "now" && tag.branches.len() == 1));
+    }
+}
@@ -1,8 +1,13 @@
 use std::convert::TryFrom;
 use std::fmt;
+use std::iter::Peekable;
+use std::ops::Range;
+use std::sync::Arc;
 
-use crate::cursor::Cursor;
 use crate::error::ParseError;
+use crate::events::{Event, Events};
+use crate::json::Json;
+use crate::syntax::{SyntaxBranch, SyntaxNode, SyntaxTag, SyntaxTree};
 
 #[derive(Debug, Clone, PartialEq)]
 pub struct ContentTree<'a> {
@@ -18,310 +23,354 @@ pub enum Node<'a> {
 #[derive(Debug, Clone, PartialEq)]
 pub struct Tag<'a> {
     pub name: &'a str,
-    // attributes: HashMap<K,V>
+    // Everything between the name and the sign, e.g. 'item in posts'.
+    pub args: &'a str,
     pub content: ContentTree<'a>,
+    // Alternative contents, such as the 'else' of an 'if'.
+    pub branches: Vec<Branch<'a>>,
 }
 
-impl<'input> TryFrom<&'input str> for ContentTree<'input> {
-    type Error = ParseError;
+// A branch splits the content of a tag, e.g. '<<if a<<x>>else<<y>>if>>'.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Branch<'a> {
+    pub name: &'a str,
+    pub args: &'a str,
+    pub content: ContentTree<'a>,
+}
 
-    fn try_from(input: &'input str) -> Result<Self, Self::Error> {
-        let cursor = &mut Cursor::new(input);
-        let content = build_content(cursor);
+// A content tree which owns its strings, so that it can outlive its input,
+// e.g. in a cache or on another thread. The strings are shared between clones.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedContentTree {
+    pub children: Vec<OwnedNode>,
+}
 
-        // If we aren't at the end of input, then something has gone wrong.
-        if cursor.first() != None {
-            let start = cursor.byte_offset();
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedNode {
+    Tag(OwnedTag),
+    Text(Arc<str>),
+}
 
-            let tag_type = match find_tag(cursor) {
-                None => panic!("Something happend and I don't know what"),
-                Some(t) => t,
-            };
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedTag {
+    pub name: Arc<str>,
+    pub args: Arc<str>,
+    pub content: OwnedContentTree,
+    pub branches: Vec<OwnedBranch>,
+}
 
-            if start != cursor.byte_offset() {
-                panic!("We really should have found a tag right after");
-            }
+#[derive(Debug, Clone, PartialEq)]
+pub struct OwnedBranch {
+    pub name: Arc<str>,
+    pub args: Arc<str>,
+    pub content: OwnedContentTree,
+}
 
-            if tag_type == TagType::Closing {
-                return Err(ParseError::NoOpeningTag);
-            }
-        }
+impl ContentTree<'_> {
+    // The tree as a JSON array of its nodes, e.g. '[{"type":"text","text":"a"}]'.
+    // Tags have a 'name', 'args', 'content' and 'branches', and branches a 'name', 'args' and 'content'.
+    pub fn to_json(&self) -> String {
+        self.json().to_string()
+    }
 
-        content
+    fn json(&self) -> Json {
+        Json::Array(self.children.iter().map(|node| match node {
+            Node::Tag(tag) => Json::object(vec![
+                ("type", Json::string("tag")),
+                ("name", Json::string(tag.name)),
+                ("args", Json::string(tag.args)),
+                ("content", tag.content.json()),
+                ("branches", Json::Array(tag.branches.iter().map(|b| Json::object(vec![
+                    ("name", Json::string(b.name)),
+                    ("args", Json::string(b.args)),
+                    ("content", b.content.json()),
+                ])).collect())),
+            ]),
+            Node::Text(text) => Json::object(vec![
+                ("type", Json::string("text")),
+                ("text", Json::string(*text)),
+            ]),
+        }).collect())
     }
-}
 
-impl fmt::Display for ContentTree<'_> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        for child in &self.children {
-            match child {
-                Node::Tag(tag) => {
-                    if tag.content.children.is_empty() {
-                        // Write the tag as self-closing.
-                        write!(f, "<<{}>>", tag.name)?
-                    } else {
-                        write!(f, "<<{}<<{}>>{}>>", tag.name, tag.content, tag.name)?
-                    }
-                },
-                Node::Text(text) => write!(f, "{}", text)?,
-            }
+    pub fn into_owned(self) -> OwnedContentTree {
+        let children = self.children.into_iter().map(|node| match node {
+            Node::Tag(tag) => OwnedNode::Tag(OwnedTag {
+                name: tag.name.into(),
+                args: tag.args.into(),
+                content: tag.content.into_owned(),
+                branches: tag.branches.into_iter().map(|b| OwnedBranch {
+                    name: b.name.into(),
+                    args: b.args.into(),
+                    content: b.content.into_owned(),
+                }).collect(),
+            }),
+            Node::Text(text) => OwnedNode::Text(text.into()),
+        }).collect();
+
+        OwnedContentTree {
+            children,
         }
-
-        Ok(())
     }
 }
 
-fn build_content<'a>(cursor: &mut Cursor<'a>) -> Result<ContentTree<'a>, ParseError> {
-    let mut content = ContentTree {
-        children: Vec::new(),
-    };
-
-    loop {
-        if let Some(text) = build_text(cursor)? {
-            content.children.push(Node::Text(text));
-        }
-
-        let tag = match find_tag(cursor) {
-            None => break, // return content
-            Some(t) => t,
-        };
-
-        if tag == TagType::Closing {
-            break; // return content
-        } else {
-            let tag = build_tag(cursor)?;
-            content.children.push(Node::Tag(tag));
+impl OwnedContentTree {
+    // Borrows the tree as a content tree, e.g. for rendering it.
+    pub fn as_tree(&self) -> ContentTree<'_> {
+        let children = self.children.iter().map(|node| match node {
+            OwnedNode::Tag(tag) => Node::Tag(Tag {
+                name: &tag.name,
+                args: &tag.args,
+                content: tag.content.as_tree(),
+                branches: tag.branches.iter().map(|b| Branch {
+                    name: &b.name,
+                    args: &b.args,
+                    content: b.content.as_tree(),
+                }).collect(),
+            }),
+            OwnedNode::Text(text) => Node::Text(text),
+        }).collect();
+
+        ContentTree {
+            children,
         }
     }
-
-    Ok(content)
 }
 
-fn build_text<'a>(cursor: &mut Cursor<'a>) -> Result<Option<&'a str>, ParseError> {
-    let start = cursor.byte_offset();
-
-    find_tag(cursor);
-
-    let end = cursor.byte_offset();
-
-    let text = &cursor.input()[start..end];
-
-    if !text.is_empty() {
-        Ok(Some(text))
-    } else {
-        Ok(None)
+impl fmt::Display for OwnedContentTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_tree())
     }
 }
 
-// Returns a Tag
-// This function must only be called when the cursor is positioned right before a tag.
-fn build_tag<'a>(cursor: &mut Cursor<'a>) -> Result<Tag<'a>, ParseError> {
-    #[cfg(debug_assertions)]
-    let pos = cursor.position();
-
-    let tag_t = find_tag(cursor);
-
-    debug_assert_eq!(pos, cursor.position());
-
-    if let Some(tag_t) = tag_t {
-        match tag_t {
-            TagType::Opening => {
-                skip_sign(cursor);
-
-                let name = parse_tag_name(cursor);
-
-                // let attributes = parse_tag_attributes();
-                
-                skip_sign(cursor);
-
-                let content = build_content(cursor)?;
-
-                // TODO we should be right before the closing tag here
-                match find_tag(cursor) {
-                    None => return Err(ParseError::NoClosingTag),
-                    Some(t) => {
-                        if t == TagType::Closing {
-                            skip_sign(cursor);
-
-                            let closing_name = parse_tag_name(cursor);
-
-                            skip_sign(cursor);
+// An error together with where it was found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    pub error: ParseError,
+    // The bytes of the offending tag or sign
+    pub span: Range<usize>,
+    // The bytes of the opening tag, if the error is about its closing tag
+    pub opening: Option<Range<usize>>,
+}
 
-                            if closing_name != name {
-                                return Err(ParseError::InvalidClosingTag);
-                            }
-                        }
-                    },
-                }
-                
-                Ok(Tag {
-                    name: name,
-                    content: content,
-                })
-            },
-            TagType::Closing => {
-                unreachable!();
-            },
-            TagType::SelfClosing => {
-                skip_sign(cursor);
+impl<'input> TryFrom<&'input str> for ContentTree<'input> {
+    type Error = ParseError;
 
-                let name = parse_tag_name(cursor);
+    fn try_from(input: &'input str) -> Result<Self, Self::Error> {
+        parse(input).map_err(|e| e.error)
+    }
+}
 
-                skip_sign(cursor);
+// Parses the input like 'try_from', but on failure also returns where the first error was found.
+pub fn parse(input: &str) -> Result<ContentTree<'_>, SyntaxError> {
+    let (content, errors) = parse_recovering(input);
 
-                Ok(Tag {
-                    name: name,
-                    content: ContentTree {
-                        children: Vec::new(),
-                    },
-                })
-            },
-        }
-    } else {
-        panic!("No tags left, you lied to me!")
+    match errors.into_iter().next() {
+        Some(e) => Err(e),
+        None => Ok(content),
     }
 }
 
-fn parse_tag_name<'a>(cursor: &mut Cursor<'a>) -> &'a str {
-    cursor.skip_while(|c| c.is_ascii_whitespace());
+// Parses the whole input, even if it contains errors, and returns all of them
+// ordered by where they were found. The parser recovers at the next tag:
+// stray closing tags and branches are skipped, and a closing tag of an
+// enclosing tag closes the tags that were left open.
+pub fn parse_recovering(input: &str) -> (ContentTree<'_>, Vec<SyntaxError>) {
+    let (tree, errors) = parse_lossless(input);
 
-    cursor.take_while(|c| c.is_ascii_alphabetic())
+    (tree.to_content_tree(), errors)
 }
 
+// Parses the input like 'parse_recovering', but keeps every byte of it, so that
+// printing the syntax tree gives back the input.
+pub fn parse_lossless(input: &str) -> (SyntaxTree<'_>, Vec<SyntaxError>) {
+    let mut parser = Parser {
+        input,
+        events: Events::new(input).peekable(),
+        open: Vec::new(),
+        errors: Vec::new(),
+    };
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
-enum TagType {
-    Opening,
-    Closing,
-    SelfClosing,
-}
+    let tree = parser.build_content();
+
+    let mut errors = parser.errors;
+    errors.sort_by_key(|e| e.span.start);
 
-// Put the cursor before the next tag
-fn find_tag(cursor: &mut Cursor) -> Option<TagType> {
-    let first = find_sign(cursor);
+    (tree, errors)
+}
 
-    let mut c = cursor.clone();
+impl fmt::Display for ContentTree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                Node::Tag(tag) => {
+                    if tag.content.children.is_empty() && tag.branches.is_empty() {
+                        // Write the tag as self-closing.
+                        write!(f, "<<{}>>", Signature(tag.name, tag.args))?
+                    } else {
+                        write!(f, "<<{}<<{}", Signature(tag.name, tag.args), tag.content)?;
 
-    c.skip(2);
+                        for branch in &tag.branches {
+                            write!(f, ">>{}<<{}", Signature(branch.name, branch.args), branch.content)?;
+                        }
 
-    let second = find_sign(&mut c);
+                        write!(f, ">>{}>>", tag.name)?
+                    }
+                },
+                Node::Text(text) => write!(f, "{}", text)?,
+            }
+        }
 
-    match (first, second) {
-        (None, None) => None,
-        (None, _) => unreachable!(),
-        (s, None) => panic!("No matching sign for {:?}", s),
-        (Some(a), Some(b)) => match (a, b) {
-            (Sign::Opening, Sign::Opening) => Some(TagType::Opening),
-            (Sign::Closing, Sign::Closing) => Some(TagType::Closing),
-            (Sign::Opening, Sign::Closing) => Some(TagType::SelfClosing),
-            _ => panic!("Invalid combination of signs"),
-        },
+        Ok(())
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-enum Sign {
-    Opening,
-    Closing,
-}
+// The name of a tag or branch followed by its arguments, if there are any.
+pub struct Signature<'a>(pub &'a str, pub &'a str);
 
-// Puts the cursor right before the next sign and returns the type of sign.
-fn find_sign(cursor: &mut Cursor) -> Option<Sign> {
-    loop {
-        match (cursor.first(), cursor.second()) {
-            (Some(a), Some(b)) => match (a, b) {
-                ('<', '<') => return Some(Sign::Opening),
-                ('>', '>') => return Some(Sign::Closing),
-                (_, '<') | (_, '>') => cursor.skip(1),
-                (_, _) => cursor.skip(2),
-            },
-            (Some(_), None) => {
-                cursor.skip(1);
-                break;
-            },
-            _ => break,
+impl fmt::Display for Signature<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.1.is_empty() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "{} {}", self.0, self.1)
         }
     }
-
-    None
 }
 
-// Put the cursor after the next sign
-fn skip_sign(cursor: &mut Cursor) {
-    find_sign(cursor);
-    cursor.skip(2);
+struct Parser<'a> {
+    input: &'a str,
+    events: Peekable<Events<'a>>,
+    // The names of the tags which haven't been closed yet, the innermost last.
+    open: Vec<&'a str>,
+    errors: Vec<SyntaxError>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn no_signs() {
-        let mut cursor = Cursor::new("abc");
+impl<'a> Parser<'a> {
+    fn error(&mut self, error: ParseError, span: Range<usize>, opening: Option<Range<usize>>) {
+        self.errors.push(SyntaxError {
+            error,
+            span,
+            opening,
+        });
+    }
 
-        let sign = find_sign(&mut cursor);
+    // The byte offset of the next event
+    fn offset(&mut self) -> usize {
+        let end = self.input.len();
 
-        // When there are no signs to be found,
-        // the cursor should be at the end of the input
-        // and the returned value should be None.
-        assert!(sign.is_none());
-        assert_eq!(cursor.first(), None);
+        self.events.peek().map_or(end, |e| e.span().start)
     }
 
-    #[test]
-    fn only_one_opening_sign() {
-        let mut cursor = Cursor::new("<<");
+    fn build_content(&mut self) -> SyntaxTree<'a> {
+        let mut content = SyntaxTree {
+            children: Vec::new(),
+        };
 
-        let sign = find_sign(&mut cursor);
+        loop {
+            // A closing tag or branch ends the content of the innermost open tag.
+            let inside = !self.open.is_empty();
+            let event = self.events.next_if(|e| !(inside && matches!(e, Event::Close { .. } | Event::Branch { .. })));
+
+            match event {
+                None => break, // return content
+                Some(Event::Text(span)) => content.children.push(SyntaxNode::Text(&self.input[span])),
+                Some(Event::Unmatched(span)) => {
+                    // Keep the sign as text.
+                    self.error(ParseError::UnmatchedSign, span.clone(), None);
+                    content.children.push(SyntaxNode::Text(&self.input[span]));
+                },
+                Some(Event::Close { span, .. }) => {
+                    self.error(ParseError::NoOpeningTag, span.clone(), None);
+                    content.children.push(SyntaxNode::Invalid(&self.input[span]));
+                },
+                Some(Event::Branch { span, .. }) => {
+                    self.error(ParseError::UnexpectedBranch, span.clone(), None);
+                    content.children.push(SyntaxNode::Invalid(&self.input[span]));
+                },
+                Some(event) => {
+                    let tag = self.build_tag(event);
+                    content.children.push(SyntaxNode::Tag(tag));
+                },
+            }
+        }
 
-        assert_eq!(sign, Some(Sign::Opening));
-        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
-        assert_eq!(cursor.input().chars().count() - cursor.position(), 2);
+        content
     }
 
-    #[test]
-    fn only_two_opening_signs() {
-        let mut cursor = Cursor::new("<<<<");
+    // Returns a Tag
+    // This function must only be called with the event of an opening or self-closing tag.
+    fn build_tag(&mut self, event: Event<'a>) -> SyntaxTag<'a> {
+        let (name, args, opening, block) = match event {
+            Event::Open { name, args, span } => (name, args, span, true),
+            Event::SelfClosing { name, args, span } => (name, args, span, false),
+            _ => unreachable!(),
+        };
 
-        let sign = find_sign(&mut cursor);
+        let mut tag = SyntaxTag {
+            span: opening.clone(),
+            open: &self.input[opening.clone()],
+            name,
+            args,
+            content: None,
+            branches: Vec::new(),
+            close: "",
+        };
 
-        assert_eq!(sign, Some(Sign::Opening));
-        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
-        assert_eq!(cursor.position(), 0);
+        if !block {
+            return tag;
+        }
 
-        cursor.skip(2);
+        self.open.push(name);
 
-        let sign = find_sign(&mut cursor);
+        tag.content = Some(self.build_content());
 
-        assert_eq!(sign, Some(Sign::Opening));
-        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
-        assert_eq!(cursor.position(), 2);
-    }
+        // We should be right before either a branch or the closing tag here
+        loop {
+            // The closing tag of an enclosing tag, so this one was never closed.
+            let enclosing = &self.open[..self.open.len() - 1];
+            let event = self.events.next_if(|e| !matches!(e, Event::Close { name: n, .. } if *n != name && enclosing.contains(n)));
 
-    #[test]
-    fn one_opening_sign() {
-        let mut cursor = Cursor::new("abc<<def");
+            match event {
+                None => {
+                    self.error(ParseError::NoClosingTag, opening, None);
+                    break;
+                },
+                Some(Event::Branch { name, args, span }) => {
+                    let content = self.build_content();
+
+                    tag.branches.push(SyntaxBranch {
+                        open: &self.input[span],
+                        name,
+                        args,
+                        content,
+                    });
+                },
+                Some(Event::Close { name: closing_name, span }) => {
+                    tag.close = &self.input[span.clone()];
 
-        let sign = find_sign(&mut cursor);
+                    if closing_name != name {
+                        self.error(ParseError::InvalidClosingTag, span, Some(opening));
+                    }
 
-        assert_eq!(sign, Some(Sign::Opening));
-        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
-        assert_eq!(cursor.position(), 3);
-    }
+                    break;
+                },
+                // The content only ends before a closing tag or a branch.
+                Some(_) => unreachable!(),
+            }
+        }
 
-    #[test]
-    fn find_tag_moves_to_end_without_any_tags_left() {
-        let mut cursor = Cursor::new("<<abc<<def");
-        
-        skip_sign(&mut cursor);
-        skip_sign(&mut cursor);
+        self.open.pop();
 
-        let tag_t = find_tag(&mut cursor);
+        tag.span.end = self.offset();
 
-        assert_eq!(tag_t, None);
-        assert_eq!(cursor.next(), None);
+        tag
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
     #[test]
     fn only_text_content() {
@@ -349,9 +398,11 @@ mod tests {
 
         eq.children.push(Node::Tag(Tag {
             name: "foo",
+            args: "",
             content: ContentTree {
                 children: vec![Node::Text("bar")],
-            }
+            },
+            branches: Vec::new(),
         }));
 
         assert_eq!(content, eq);
@@ -368,9 +419,11 @@ mod tests {
         let eq = ContentTree {
             children: vec![Node::Tag(Tag {
                 name: "foo",
+                args: "",
                 content: ContentTree {
                     children: Vec::new()
                 },
+                branches: Vec::new(),
             })],
         };
 
@@ -398,6 +451,48 @@ mod tests {
         assert_eq!(content, Err(ParseError::NoOpeningTag));
     }
 
+    #[test]
+    fn offset_of_error() {
+        let e = parse("ab<<foo<<bar").unwrap_err();
+        assert_eq!((e.error, e.span), (ParseError::NoClosingTag, 2..9));
+
+        let e = parse("<<foo<<bar>>baz>>").unwrap_err();
+        assert_eq!((e.error, e.span, e.opening), (ParseError::InvalidClosingTag, 10..17, Some(0..7)));
+
+        let e = parse("a\n>>else<<b").unwrap_err();
+        assert_eq!((e.error, e.span), (ParseError::UnexpectedBranch, 2..10));
+    }
+
+    #[test]
+    fn recovers_after_errors() {
+        let (content, errors) = parse_recovering("a>>x>>b<<c<<d>>e>><<f>>");
+
+        let errors: Vec<_> = errors.into_iter().map(|e| (e.error, e.span.start)).collect();
+
+        assert_eq!(errors, vec![(ParseError::NoOpeningTag, 1), (ParseError::InvalidClosingTag, 13)]);
+        assert_eq!(format!("{}", content), "ab<<c<<d>>c>><<f>>");
+    }
+
+    #[test]
+    fn closing_tag_of_enclosing_tag() {
+        let (content, errors) = parse_recovering("<<a<<<<b<<x>>a>>y");
+
+        let errors: Vec<_> = errors.into_iter().map(|e| (e.error, e.span.start)).collect();
+
+        assert_eq!(errors, vec![(ParseError::NoClosingTag, 5)]);
+        assert_eq!(format!("{}", content), "<<a<<<<b<<x>>b>>>>a>>y");
+    }
+
+    #[test]
+    fn unmatched_sign() {
+        let (content, errors) = parse_recovering("a << b");
+
+        let errors: Vec<_> = errors.into_iter().map(|e| (e.error, e.span)).collect();
+
+        assert_eq!(errors, vec![(ParseError::UnmatchedSign, 2..4)]);
+        assert_eq!(format!("{}", content), "a << b");
+    }
+
     #[test]
     fn displays_correctly() {
         let s = "<<foo<<bar<<baz>>>>foo>>";
@@ -418,4 +513,102 @@ mod tests {
 
         assert_eq!(format!("{}", content), o);
     }
+
+    #[test]
+    fn tag_with_arguments() {
+        let content = ContentTree::try_from("<<for post in posts<<x>>for>>").unwrap();
+
+        match &content.children[0] {
+            Node::Tag(tag) => {
+                assert_eq!(tag.name, "for");
+                assert_eq!(tag.args, "post in posts");
+            },
+            _ => panic!("Expected a tag"),
+        }
+    }
+
+    #[test]
+    fn tag_name_with_dots() {
+        let content = ContentTree::try_from("<<page.title>>").unwrap();
+
+        match &content.children[0] {
+            Node::Tag(tag) => {
+                assert_eq!(tag.name, "page.title");
+                assert_eq!(tag.args, "");
+            },
+            _ => panic!("Expected a tag"),
+        }
+    }
+
+    #[test]
+    fn tag_with_branch() {
+        let content = ContentTree::try_from("<<if page.draft<<a>>else<<b>>if>>").unwrap();
+
+        let eq = ContentTree {
+            children: vec![Node::Tag(Tag {
+                name: "if",
+                args: "page.draft",
+                content: ContentTree {
+                    children: vec![Node::Text("a")],
+                },
+                branches: vec![Branch {
+                    name: "else",
+                    args: "",
+                    content: ContentTree {
+                        children: vec![Node::Text("b")],
+                    },
+                }],
+            })],
+        };
+
+        assert_eq!(content, eq);
+    }
+
+    #[test]
+    fn unclosed_branch() {
+        let content = ContentTree::try_from("<<if a<<b>>else<<c");
+
+        assert_eq!(content, Err(ParseError::NoClosingTag));
+    }
+
+    #[test]
+    fn branch_without_opening_tag() {
+        let content = ContentTree::try_from("a>>else<<b");
+
+        assert_eq!(content, Err(ParseError::UnexpectedBranch));
+    }
+
+    #[test]
+    fn displays_arguments_and_branches() {
+        let s = "<<for p in posts<<<<p.title>>>>else<<None>>for>>";
+        let content = ContentTree::try_from(s).unwrap();
+
+        assert_eq!(format!("{}", content), s);
+    }
+
+    #[test]
+    fn owned_tree_outlives_input() {
+        let s = "a<<if b<<<<c>>>>else<<d>>if>>";
+
+        let owned = {
+            let input = s.to_owned();
+            ContentTree::try_from(input.as_ref()).unwrap().into_owned()
+        };
+
+        // The tree can be sent to other threads.
+        let owned = std::thread::spawn(move || owned.clone()).join().unwrap();
+
+        assert_eq!(owned.as_tree(), ContentTree::try_from(s).unwrap());
+        assert_eq!(owned.to_string(), s);
+    }
+
+    #[test]
+    fn serializes_to_json() {
+        let content = ContentTree::try_from("a<<if b<<c>>else<<>>if>>").unwrap();
+
+        assert_eq!(content.to_json(), concat!(
+            r#"[{"text":"a","type":"text"},{"args":"b","branches":[{"args":"","content":[],"name":"else"}],"#,
+            r#""content":[{"text":"c","type":"text"}],"name":"if","type":"tag"}]"#,
+        ));
+    }
 }
@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    String(String),
+    List(Vec<Value>),
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    // Decides which branch of an 'if' is taken.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            Value::String(s) => !s.is_empty(),
+            Value::List(l) => !l.is_empty(),
+            Value::Map(m) => !m.is_empty(),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Value::Map(m) => m.get(key),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::String(s) => write!(f, "{}", s),
+            Value::List(l) => {
+                for (i, v) in l.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+
+                    write!(f, "{}", v)?;
+                }
+
+                Ok(())
+            },
+            // A map has no sensible textual representation.
+            Value::Map(_) => Ok(()),
+        }
+    }
+}
+
+// The variables available while rendering.
+// Every 'for' adds a scope, so that the loop variable shadows outer variables.
+#[derive(Debug, Clone)]
+pub struct Context {
+    scopes: Vec<HashMap<String, Value>>,
+}
+
+impl Context {
+    pub fn new() -> Context {
+        Context {
+            scopes: vec![HashMap::new()],
+        }
+    }
+
+    pub fn insert<S: Into<String>>(&mut self, name: S, value: Value) {
+        self.scopes.last_mut().unwrap().insert(name.into(), value);
+    }
+
+    // Looks up a dotted path, e.g. 'page.title'.
+    pub fn lookup(&self, path: &str) -> Option<&Value> {
+        let mut parts = path.split('.');
+
+        let root = parts.next()?;
+
+        let mut value = self.scopes.iter().rev().find_map(|s| s.get(root))?;
+
+        for part in parts {
+            value = value.get(part)?;
+        }
+
+        Some(value)
+    }
+
+    pub fn push_scope(&mut self) {
+        self.scopes.push(HashMap::new());
+    }
+
+    pub fn pop_scope(&mut self) {
+        debug_assert!(self.scopes.len() > 1, "Can't pop the outermost scope");
+        self.scopes.pop();
+    }
+}
+
+impl Default for Context {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page() -> Value {
+        let mut m = BTreeMap::new();
+        m.insert("title".to_owned(), Value::String("Hello".to_owned()));
+        m.insert("draft".to_owned(), Value::Bool(false));
+        Value::Map(m)
+    }
+
+    #[test]
+    fn lookup_dotted_path() {
+        let mut ctx = Context::new();
+        ctx.insert("page", page());
+
+        assert_eq!(ctx.lookup("page.title"), Some(&Value::String("Hello".to_owned())));
+        assert_eq!(ctx.lookup("page.missing"), None);
+        assert_eq!(ctx.lookup("missing"), None);
+    }
+
+    #[test]
+    fn inner_scope_shadows_outer() {
+        let mut ctx = Context::new();
+        ctx.insert("a", Value::Bool(false));
+
+        ctx.push_scope();
+        ctx.insert("a", Value::Bool(true));
+        assert_eq!(ctx.lookup("a"), Some(&Value::Bool(true)));
+
+        ctx.pop_scope();
+        assert_eq!(ctx.lookup("a"), Some(&Value::Bool(false)));
+    }
+
+    #[test]
+    fn truthiness() {
+        assert!(!Value::String(String::new()).is_truthy());
+        assert!(Value::String("a".to_owned()).is_truthy());
+        assert!(!Value::List(Vec::new()).is_truthy());
+        assert!(!Value::Bool(false).is_truthy());
+    }
+
+    #[test]
+    fn displays_list_comma_separated() {
+        let v = Value::List(vec![Value::String("a".to_owned()), Value::String("b".to_owned())]);
+
+        assert_eq!(format!("{}", v), "a, b");
+    }
+}
@@ -3,23 +3,22 @@
 #[derive(Clone)]
 pub struct Cursor<'input> {
     input: &'input str,
-    // The char position
-    pos: usize,
-    // The byte position
+    // The byte position, the char position is only counted when asked for
     byte_pos: usize,
 }
 
 impl<'input> Cursor<'input> {
-    pub fn new(input: &str) -> Cursor {
+    pub fn new(input: &'input str) -> Cursor<'input> {
         Cursor {
-            input: input,
-            pos: 0,
+            input,
             byte_pos: 0,
         }
     }
 
+    // The char position, which is slow to get since the chars before it are counted.
+    #[cfg(test)]
     pub fn position(&self) -> usize {
-        self.pos
+        self.input[..self.byte_pos].chars().count()
     }
 
     pub fn byte_offset(&self) -> usize {
@@ -30,11 +29,17 @@ impl<'input> Cursor<'input> {
         self.input
     }
 
+    // Moves the cursor to the byte offset, which must be at the start of a char.
+    pub fn seek(&mut self, byte_offset: usize) {
+        debug_assert!(self.input.is_char_boundary(byte_offset));
+
+        self.byte_pos = byte_offset;
+    }
+
     pub fn next(&mut self) -> Option<char> {
         let c = self.input[self.byte_pos..].chars().next();
 
         if let Some(c) = c {
-            self.pos += 1;
             self.byte_pos += c.len_utf8();
         }
 
@@ -58,10 +63,7 @@ impl<'input> Cursor<'input> {
         for _ in 0..n {
             match chars.next() {
                 None => break,
-                Some(c) => {
-                    self.pos += 1;
-                    self.byte_pos += c.len_utf8();
-                },
+                Some(c) => self.byte_pos += c.len_utf8(),
             }
         }
     }
@@ -133,10 +135,18 @@ mod tests {
     fn next_increments_byte_pos_correctly_for_utf8() {
         let mut c = Cursor::new("åäö");
         c.next();
-        assert_eq!(c.pos, 1);
+        assert_eq!(c.position(), 1);
         assert_eq!(c.byte_pos, 2);
     }
 
+    #[test]
+    fn seek_moves_to_byte_offset() {
+        let mut c = Cursor::new("åäö");
+        c.seek(4);
+        assert_eq!(c.position(), 2);
+        assert_eq!(c.next(), Some('ö'));
+    }
+
     #[test]
     fn nth_wont_consume() {
         let c = Cursor::new("abcdef");
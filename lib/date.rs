@@ -0,0 +1,63 @@
+use chrono::DateTime;
+use chrono::FixedOffset;
+use chrono::NaiveDate;
+use chrono::NaiveDateTime;
+use chrono::TimeZone;
+use chrono::Utc;
+
+// Parses the dates we accept in front matter.
+// Dates without a time are at midnight, and dates without an offset are in UTC.
+pub fn parse(s: &str) -> Option<DateTime<FixedOffset>> {
+    let s = s.trim();
+
+    if let Ok(d) = DateTime::parse_from_rfc3339(s) {
+        return Some(d);
+    }
+
+    let utc = FixedOffset::east_opt(0).unwrap();
+
+    for format in &["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M"] {
+        if let Ok(d) = NaiveDateTime::parse_from_str(s, format) {
+            return utc.from_local_datetime(&d).single();
+        }
+    }
+
+    let d = NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()?;
+
+    utc.from_local_datetime(&d.and_hms_opt(0, 0, 0)?).single()
+}
+
+pub fn now() -> DateTime<FixedOffset> {
+    Utc::now().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_date_only() {
+        let d = parse("2020-10-01").unwrap();
+
+        assert_eq!(d.to_rfc3339(), "2020-10-01T00:00:00+00:00");
+    }
+
+    #[test]
+    fn parses_rfc3339() {
+        let d = parse("2020-10-01T12:30:00+02:00").unwrap();
+
+        assert_eq!(d.to_rfc3339(), "2020-10-01T12:30:00+02:00");
+    }
+
+    #[test]
+    fn parses_date_and_time() {
+        let d = parse("2020-10-01 12:30").unwrap();
+
+        assert_eq!(d.to_rfc3339(), "2020-10-01T12:30:00+00:00");
+    }
+
+    #[test]
+    fn rejects_garbage() {
+        assert_eq!(parse("yesterday"), None);
+    }
+}
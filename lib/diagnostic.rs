@@ -0,0 +1,480 @@
+use std::fmt;
+use std::fmt::Write;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use crate::content_tree::SyntaxError;
+use crate::cursor::Cursor;
+use crate::error::Error;
+use crate::error::FrontMatterError;
+use crate::error::ParseError;
+use crate::json::Json;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    // Something which is probably a mistake, but doesn't stop the build.
+    Warning,
+    Error,
+}
+
+// A problem found in a file of the project.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    // The line of the file, if the problem can be pointed to
+    pub line: Option<usize>,
+    pub severity: Severity,
+    // A stable code of the kind of problem, e.g. 'E003'
+    pub code: Option<&'static str>,
+    pub message: String,
+    // The first one shows the problem itself, any others show related places.
+    pub snippets: Vec<Snippet>,
+    // A suggestion of how to fix the problem
+    pub hint: Option<String>,
+}
+
+// Where an input starts in its file, e.g. the body of a page after its front matter.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Origin {
+    // Starting at 1
+    pub line: usize,
+    // In bytes
+    pub offset: usize,
+}
+
+impl Origin {
+    pub const START: Origin = Origin {
+        line: 1,
+        offset: 0,
+    };
+}
+
+// A line of a file to show in a diagnostic, with a part of it marked.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Snippet {
+    pub line: usize,
+    // The first marked char, starting at 1
+    pub column: usize,
+    // The number of marked chars
+    pub length: usize,
+    // The marked bytes of the file
+    pub start: usize,
+    pub end: usize,
+    // The whole line, without the line break
+    pub source: String,
+    pub label: Option<String>,
+}
+
+impl Diagnostic {
+    pub fn error<P: Into<PathBuf>, S: Into<String>>(path: P, line: Option<usize>, message: S) -> Diagnostic {
+        Diagnostic {
+            path: path.into(),
+            line,
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            snippets: Vec::new(),
+            hint: None,
+        }
+    }
+
+    pub fn warning<P: Into<PathBuf>, S: Into<String>>(path: P, line: Option<usize>, message: S) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Warning,
+            ..Diagnostic::error(path, line, message)
+        }
+    }
+
+    pub fn with_code(mut self, code: &'static str) -> Diagnostic {
+        self.code = Some(code);
+        self
+    }
+
+    pub fn with_snippet(mut self, snippet: Snippet) -> Diagnostic {
+        self.snippets.push(snippet);
+        self
+    }
+
+    pub fn with_hint<S: Into<String>>(mut self, hint: S) -> Diagnostic {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    // A syntax error in the input, which starts at 'origin' in the file.
+    pub fn syntax<P: Into<PathBuf>>(path: P, input: &str, origin: Origin, e: &SyntaxError) -> Diagnostic {
+        let snippet = Snippet::new(input, e.span.clone(), origin);
+
+        let hint = match e.error {
+            ParseError::NoOpeningTag => "remove the closing tag, or add an opening tag before it".to_owned(),
+            ParseError::NoClosingTag => format!("close the tag with '>>{}>>'", tag_name(&input[e.span.clone()])),
+            ParseError::InvalidClosingTag => match &e.opening {
+                Some(opening) => format!("expected '>>{}>>'", tag_name(&input[opening.clone()])),
+                None => "the closing tag must have the name of the opening tag".to_owned(),
+            },
+            ParseError::UnexpectedBranch => "branches such as 'else' can only be used inside a tag".to_owned(),
+            ParseError::UnmatchedSign => "tags are written '<<name>>' or '<<name<<content>>name>>'".to_owned(),
+        };
+
+        let mut d = Diagnostic::error(path, Some(snippet.line), e.error.to_string())
+            .with_code(e.error.code())
+            .with_snippet(snippet)
+            .with_hint(hint);
+
+        if let Some(opening) = &e.opening {
+            d = d.with_snippet(Snippet::new(input, opening.clone(), origin).with_label("opening tag"));
+        }
+
+        d
+    }
+
+    // An error in front matter, showing the offending line if the source is given.
+    pub fn front_matter<P: Into<PathBuf>>(path: P, source: Option<&str>, e: FrontMatterError) -> Diagnostic {
+        let (line, hint) = match e {
+            FrontMatterError::InvalidLine(n) => (n, "front matter consists of 'key: value' lines, and '- item' lines of lists"),
+            FrontMatterError::Unclosed => (1, "end the front matter with a '---' line"),
+        };
+
+        let d = Diagnostic::error(path, Some(line), e.to_string())
+            .with_code(e.code())
+            .with_hint(hint);
+
+        let source = match source {
+            Some(source) => source,
+            None => return d,
+        };
+
+        let start: usize = source.split_inclusive('\n').take(line - 1).map(|l| l.len()).sum();
+
+        if start >= source.len() {
+            return d;
+        }
+
+        let end = source[start..].find('\n').map_or(source.len(), |i| start + i);
+
+        d.with_snippet(Snippet::new(source, start..end, Origin::START))
+    }
+
+    // Renders the diagnostic like a compiler would, with the marked lines of the file
+    // below the message. Colours are used if 'colour' is set, e.g. for a terminal.
+    pub fn render(&self, colour: bool) -> String {
+        let paint = |code: &str, text: &str| {
+            if colour {
+                format!("\x1b[{}m{}\x1b[0m", code, text)
+            } else {
+                text.to_owned()
+            }
+        };
+
+        let severity = match self.severity {
+            Severity::Warning => "1;33",
+            Severity::Error => "1;31",
+        };
+
+        let mut out = String::new();
+
+        writeln!(out, "{}{}", paint(severity, &self.severity.to_string()), paint("1", &format!(": {}", self.message))).unwrap();
+
+        let width = self.snippets.iter().map(|s| s.line.to_string().len()).max().unwrap_or(1);
+        let pad = " ".repeat(width);
+        let bar = paint("1;34", "|");
+
+        if !self.path.as_os_str().is_empty() {
+            let location = match (self.snippets.first(), self.line) {
+                (Some(s), _) => format!("{}:{}:{}", self.path.display(), s.line, s.column),
+                (None, Some(line)) => format!("{}:{}", self.path.display(), line),
+                (None, None) => format!("{}", self.path.display()),
+            };
+
+            writeln!(out, "{}{} {}", pad, paint("1;34", "-->"), location).unwrap();
+        }
+
+        if !self.snippets.is_empty() {
+            writeln!(out, "{} {}", pad, bar).unwrap();
+
+            let mut snippets: Vec<(usize, &Snippet)> = self.snippets.iter().enumerate().collect();
+            snippets.sort_by_key(|(_, s)| (s.line, s.column));
+
+            for (i, s) in snippets {
+                let number = paint("1;34", &format!("{:>w$}", s.line, w = width));
+                writeln!(out, "{} {} {}", number, bar, s.source).unwrap();
+
+                // Keep tabs, so the marks line up with the source.
+                let indent: String = s.source.chars()
+                    .take(s.column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect();
+
+                let (mark, code) = if i == 0 { ('^', severity) } else { ('-', "1;34") };
+                let mut marks: String = std::iter::repeat_n(mark, s.length).collect();
+
+                if let Some(label) = &s.label {
+                    marks = format!("{} {}", marks, label);
+                }
+
+                writeln!(out, "{} {} {}{}", pad, bar, indent, paint(code, &marks)).unwrap();
+            }
+        }
+
+        if let Some(hint) = &self.hint {
+            writeln!(out, "{} {} {}: {}", pad, paint("1;34", "="), paint("1", "hint"), hint).unwrap();
+        }
+
+        out
+    }
+}
+
+impl Diagnostic {
+    // The diagnostic as a single line of JSON, for editors and CI, e.g.
+    //
+    // {"code":"E003","column":6,"file":"base.html","hint":"...","line":2,"message":"...",
+    //  "related":[{"column":1,"line":1,"message":"opening tag",...}],"severity":"error","span":{"end":20,"start":13}}
+    //
+    // Fields which are unknown are null, and 'related' lists the other marked places.
+    pub fn to_json(&self) -> String {
+        let mut fields = vec![
+            ("severity", Json::string(self.severity.to_string())),
+            ("code", self.code.map_or(Json::Null, Json::string)),
+            ("message", Json::string(self.message.as_str())),
+            ("file", Json::string(self.path.to_string_lossy())),
+        ];
+
+        match self.snippets.first() {
+            Some(s) => fields.extend(s.json_fields()),
+            None => {
+                fields.push(("line", self.line.map_or(Json::Null, Json::from)));
+                fields.push(("column", Json::Null));
+                fields.push(("span", Json::Null));
+            },
+        }
+
+        let related = self.snippets.iter().skip(1).map(|s| {
+            let mut f = vec![("message", s.label.as_deref().map_or(Json::Null, Json::string))];
+            f.extend(s.json_fields());
+
+            Json::object(f)
+        }).collect();
+
+        fields.push(("related", Json::Array(related)));
+        fields.push(("hint", self.hint.as_deref().map_or(Json::Null, Json::string)));
+
+        Json::object(fields).to_string()
+    }
+}
+
+impl Snippet {
+    fn json_fields(&self) -> Vec<(&'static str, Json)> {
+        vec![
+            ("line", Json::from(self.line)),
+            ("column", Json::from(self.column)),
+            ("span", Json::object(vec![("start", Json::from(self.start)), ("end", Json::from(self.end))])),
+        ]
+    }
+
+    // Marks the bytes 'span' of the input, which starts at 'origin' in its file.
+    // A span over several lines is marked until the end of its first line.
+    pub fn new(input: &str, span: Range<usize>, origin: Origin) -> Snippet {
+        let (line, column) = location(input, span.start);
+
+        let line_start = input[..span.start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[span.start..].find('\n').map_or(input.len(), |i| span.start + i);
+
+        let source = input[line_start..line_end].trim_end_matches('\r');
+        let end = span.end.min(line_end);
+        let marked = &input[span.start..end];
+
+        Snippet {
+            line: origin.line + line - 1,
+            column,
+            length: marked.chars().count().max(1),
+            start: origin.offset + span.start,
+            end: origin.offset + end,
+            source: source.to_owned(),
+            label: None,
+        }
+    }
+
+    pub fn with_label<S: Into<String>>(mut self, label: S) -> Snippet {
+        self.label = Some(label.into());
+        self
+    }
+}
+
+impl Error {
+    // Errors which belong to a file become diagnostics of that file,
+    // and 'Error::Diagnostics' is split up into the diagnostics it holds.
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        let code = self.code();
+
+        let d = match self {
+            Error::Diagnostics(d) => return d,
+            Error::Parse(p, e) => Diagnostic::error(p, None, e.to_string()),
+            Error::FrontMatter(p, e) => Diagnostic::front_matter(p, None, e),
+            Error::Render(p, e) => Diagnostic::error(p, None, e.to_string()),
+            e => Diagnostic::error(PathBuf::new(), None, e.to_string()),
+        };
+
+        vec![Diagnostic {
+            code,
+            ..d
+        }]
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+// 'path:line: severity: message', leaving out whatever is unknown.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.path.as_os_str().is_empty() {
+            write!(f, "{}", self.path.display())?;
+
+            if let Some(line) = self.line {
+                write!(f, ":{}", line)?;
+            }
+
+            write!(f, ": ")?;
+        }
+
+        write!(f, "{}: {}", self.severity, self.message)
+    }
+}
+
+// The line, starting at 1, which the byte offset is on.
+pub fn line_of(input: &str, offset: usize) -> usize {
+    input[..offset].matches('\n').count() + 1
+}
+
+// The line and column, both starting at 1, of the byte offset.
+// Columns are counted in chars rather than bytes.
+pub fn location(input: &str, offset: usize) -> (usize, usize) {
+    let mut cursor = Cursor::new(input);
+
+    let mut line = 1;
+    // The byte offset of the start of the line
+    let mut start = 0;
+
+    while cursor.byte_offset() < offset {
+        match cursor.next() {
+            Some('\n') => {
+                line += 1;
+                start = cursor.byte_offset();
+            },
+            Some(_) => (),
+            None => break,
+        }
+    }
+
+    (line, cursor.input()[start..cursor.byte_offset()].chars().count() + 1)
+}
+
+// The name of the tag at the start of the input, e.g. 'foo' of '<<foo bar<<'.
+pub fn tag_name(tag: &str) -> &str {
+    let tag = tag.trim_start_matches(['<', '>']).trim_start();
+    let end = tag.find(|c: char| !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')).unwrap_or(tag.len());
+
+    &tag[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_of_offset() {
+        assert_eq!(line_of("a\nb\nc", 0), 1);
+        assert_eq!(line_of("a\nb\nc", 2), 2);
+        assert_eq!(line_of("a\nb\nc", 4), 3);
+    }
+
+    #[test]
+    fn location_counts_chars() {
+        assert_eq!(location("åäö\nab", 4), (1, 3));
+        assert_eq!(location("åäö\nab", 8), (2, 2));
+    }
+
+    #[test]
+    fn snippet_of_span() {
+        let s = Snippet::new("a\nb <<c<<d\ne", 4..9, Origin { line: 10, offset: 20 });
+
+        assert_eq!((s.line, s.column, s.length), (11, 3, 5));
+        assert_eq!((s.start, s.end), (24, 29));
+        assert_eq!(s.source, "b <<c<<d");
+    }
+
+    #[test]
+    fn renders_syntax_error() {
+        let input = "<<foo<<\n  bar>>baz>>";
+        let e = crate::content_tree::parse(input).unwrap_err();
+
+        let d = Diagnostic::syntax("base.html", input, Origin::START, &e);
+
+        assert_eq!(d.render(false), "\
+error: closing tag does not match the opening tag
+ --> base.html:2:6
+  |
+1 | <<foo<<
+  | ------- opening tag
+2 |   bar>>baz>>
+  |      ^^^^^^^
+  = hint: expected '>>foo>>'
+");
+    }
+
+    #[test]
+    fn renders_without_snippet() {
+        let d = Diagnostic::warning("index.html", None, "unknown variable 'page.titel'");
+
+        assert_eq!(d.render(false), "warning: unknown variable 'page.titel'\n --> index.html\n");
+        assert!(d.render(true).contains("\x1b[1;33mwarning\x1b[0m"));
+    }
+
+    #[test]
+    fn json() {
+        let input = "<<foo<<\n  bar>>baz>>";
+        let e = crate::content_tree::parse(input).unwrap_err();
+
+        let d = Diagnostic::syntax("base.html", input, Origin::START, &e);
+
+        assert_eq!(d.to_json(), concat!(
+            r#"{"code":"E003","column":6,"file":"base.html","hint":"expected '>>foo>>'","line":2,"#,
+            r#""message":"closing tag does not match the opening tag","#,
+            r#""related":[{"column":1,"line":1,"message":"opening tag","span":{"end":7,"start":0}}],"#,
+            r#""severity":"error","span":{"end":20,"start":13}}"#,
+        ));
+
+        let d = Diagnostic::warning("index.html", None, "unknown variable 'page.titel'").with_code("W001");
+
+        assert_eq!(d.to_json(), concat!(
+            r#"{"code":"W001","column":null,"file":"index.html","hint":null,"line":null,"#,
+            r#""message":"unknown variable 'page.titel'","related":[],"severity":"warning","span":null}"#,
+        ));
+    }
+
+    #[test]
+    fn front_matter_line() {
+        let d = Diagnostic::front_matter("a.html", Some("---\nx: 1\nnonsense\n---\n"), FrontMatterError::InvalidLine(3));
+
+        assert_eq!(d.snippets[0].source, "nonsense");
+        assert_eq!(d.snippets[0].length, 8);
+    }
+
+    #[test]
+    fn display() {
+        let d = Diagnostic::error("templates/base.html", Some(3), "opening tag without a closing tag");
+        assert_eq!(d.to_string(), "templates/base.html:3: error: opening tag without a closing tag");
+
+        let d = Diagnostic::warning("content/index.html", None, "unknown variable 'page.titel'");
+        assert_eq!(d.to_string(), "content/index.html: warning: unknown variable 'page.titel'");
+    }
+}
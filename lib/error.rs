@@ -1,6 +1,192 @@
-#[derive(Debug, PartialEq, Eq)]
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::diagnostic::Diagnostic;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ParseError {
     NoOpeningTag,
     NoClosingTag,
     InvalidClosingTag,
+    UnexpectedBranch,
+    // A '<<' or '>>' which isn't part of a tag.
+    UnmatchedSign,
 }
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrontMatterError {
+    Unclosed,
+    // The line number of a line that isn't 'key: value' or a list item.
+    InvalidLine(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenderError {
+    // A 'for' over a variable that isn't a list.
+    UnknownCollection(String),
+    // A 'for' whose arguments aren't 'item in collection'.
+    InvalidLoop(String),
+    // An 'if' or 'elif' without a condition.
+    InvalidCondition,
+    // A branch that its tag doesn't support, e.g. 'else' in a module.
+    UnexpectedBranch(String),
+    // A module that (indirectly) includes itself.
+    ModuleRecursion(String),
+    // Filters that can't be parsed, e.g. a missing quote.
+    InvalidFilter(String),
+    UnknownFilter(String),
+    // The name of the filter and the reason it failed.
+    Filter(String, String),
+    // The name of the custom tag and the reason its handler failed.
+    Tag(String, String),
+}
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Parse(PathBuf, ParseError),
+    FrontMatter(PathBuf, FrontMatterError),
+    Render(PathBuf, RenderError),
+    // A template needed to generate a page, e.g. for a taxonomy.
+    MissingTemplate(String),
+    // Settings which can't be used together, or an invalid setting.
+    Config(String),
+    // A directory which 'clean' won't remove, and why.
+    RefusedToRemove(PathBuf, &'static str),
+    // Problems found in the files of the project, sorted by file and line.
+    Diagnostics(Vec<Diagnostic>),
+    // A plugin which failed, and why.
+    Plugin(String),
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+// Codes identify the kind of an error, e.g. for editors, and never change.
+// Errors in the syntax start at E001, in front matter at E010, while rendering at E020,
+// in the templates at E030, in the settings or file system at E040 and in plugins at E050.
+impl ParseError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::NoOpeningTag => "E001",
+            ParseError::NoClosingTag => "E002",
+            ParseError::InvalidClosingTag => "E003",
+            ParseError::UnexpectedBranch => "E004",
+            ParseError::UnmatchedSign => "E005",
+        }
+    }
+}
+
+impl FrontMatterError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            FrontMatterError::Unclosed => "E010",
+            FrontMatterError::InvalidLine(_) => "E011",
+        }
+    }
+}
+
+impl RenderError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            RenderError::UnknownCollection(_) => "E020",
+            RenderError::InvalidLoop(_) => "E021",
+            RenderError::InvalidCondition => "E022",
+            RenderError::UnexpectedBranch(_) => "E023",
+            RenderError::ModuleRecursion(_) => "E024",
+            RenderError::InvalidFilter(_) => "E025",
+            RenderError::UnknownFilter(_) => "E026",
+            RenderError::Filter(_, _) => "E027",
+            RenderError::Tag(_, _) => "E028",
+        }
+    }
+}
+
+// A template which (indirectly) extends itself.
+pub const TEMPLATE_RECURSION: &str = "E030";
+// A reference to a variable which doesn't exist.
+pub const UNKNOWN_VARIABLE: &str = "W001";
+// A page extending a template which doesn't exist.
+pub const UNKNOWN_TEMPLATE: &str = "W002";
+
+impl Error {
+    // The code of the error, except for 'Error::Diagnostics' which holds several.
+    pub fn code(&self) -> Option<&'static str> {
+        let code = match self {
+            Error::Parse(_, e) => e.code(),
+            Error::FrontMatter(_, e) => e.code(),
+            Error::Render(_, e) => e.code(),
+            Error::MissingTemplate(_) => "E031",
+            Error::Config(_) => "E040",
+            Error::Io(_) => "E041",
+            Error::RefusedToRemove(_, _) => "E042",
+            Error::Plugin(_) => "E050",
+            Error::Diagnostics(_) => return None,
+        };
+
+        Some(code)
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::NoOpeningTag => write!(f, "closing tag without an opening tag"),
+            ParseError::NoClosingTag => write!(f, "opening tag without a closing tag"),
+            ParseError::InvalidClosingTag => write!(f, "closing tag does not match the opening tag"),
+            ParseError::UnexpectedBranch => write!(f, "branch outside of a tag"),
+            ParseError::UnmatchedSign => write!(f, "sign without a matching sign"),
+        }
+    }
+}
+
+impl fmt::Display for FrontMatterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrontMatterError::Unclosed => write!(f, "front matter is never closed"),
+            FrontMatterError::InvalidLine(n) => write!(f, "invalid front matter on line {}", n),
+        }
+    }
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RenderError::UnknownCollection(c) => write!(f, "unknown collection '{}'", c),
+            RenderError::InvalidLoop(a) => write!(f, "expected 'item in collection', found '{}'", a),
+            RenderError::InvalidCondition => write!(f, "missing condition"),
+            RenderError::UnexpectedBranch(b) => write!(f, "unexpected branch '{}'", b),
+            RenderError::ModuleRecursion(m) => write!(f, "module '{}' includes itself", m),
+            RenderError::InvalidFilter(a) => write!(f, "invalid filters '{}'", a),
+            RenderError::UnknownFilter(n) => write!(f, "unknown filter '{}'", n),
+            RenderError::Filter(n, e) => write!(f, "filter '{}' failed: {}", n, e),
+            RenderError::Tag(n, e) => write!(f, "tag '{}' failed: {}", n, e),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Parse(p, e) => write!(f, "{}: {}", p.display(), e),
+            Error::FrontMatter(p, e) => write!(f, "{}: {}", p.display(), e),
+            Error::Render(p, e) => write!(f, "{}: {}", p.display(), e),
+            Error::MissingTemplate(t) => write!(f, "missing template '{}'", t),
+            Error::Config(e) => write!(f, "{}", e),
+            Error::RefusedToRemove(p, why) => write!(f, "refusing to remove {}: {}", p.display(), why),
+            Error::Plugin(e) => write!(f, "plugin failed: {}", e),
+            Error::Diagnostics(d) => {
+                let lines: Vec<String> = d.iter().map(|d| d.to_string()).collect();
+
+                write!(f, "{}", lines.join("\n"))
+            },
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,563 @@
+use std::io;
+use std::io::Read;
+use std::ops::Range;
+use std::str;
+use std::sync::Arc;
+
+use crate::cursor::Cursor;
+
+// How much is read at a time by 'ReadEvents'
+const CHUNK: usize = 8 * 1024;
+
+// What a pull parser finds in the input, in order, e.g. for tools which only need
+// to know where the tags are. The events don't tell whether the tags are nested
+// correctly, which building the content tree from them does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event<'a> {
+    // An opening tag, e.g. '<<if page.draft<<', which its content follows.
+    Open { name: &'a str, args: &'a str, span: Range<usize> },
+    // A closing tag, e.g. '>>if>>'
+    Close { name: &'a str, span: Range<usize> },
+    // A tag without content, e.g. '<<page.title>>'
+    SelfClosing { name: &'a str, args: &'a str, span: Range<usize> },
+    // A branch, e.g. '>>else<<', which ends a content and starts the next.
+    Branch { name: &'a str, args: &'a str, span: Range<usize> },
+    // The bytes of the text between two tags
+    Text(Range<usize>),
+    // A sign without a sign after it to make a tag, which is text as well.
+    Unmatched(Range<usize>),
+}
+
+// An event which owns its strings, see 'ReadEvents'.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedEvent {
+    Open { name: Arc<str>, args: Arc<str>, span: Range<usize> },
+    Close { name: Arc<str>, span: Range<usize> },
+    SelfClosing { name: Arc<str>, args: Arc<str>, span: Range<usize> },
+    Branch { name: Arc<str>, args: Arc<str>, span: Range<usize> },
+    Text(Range<usize>),
+    Unmatched(Range<usize>),
+}
+
+impl Event<'_> {
+    // The bytes of the input the event was found at.
+    pub fn span(&self) -> Range<usize> {
+        match self {
+            Event::Open { span, .. } |
+            Event::Close { span, .. } |
+            Event::SelfClosing { span, .. } |
+            Event::Branch { span, .. } |
+            Event::Text(span) |
+            Event::Unmatched(span) => span.clone(),
+        }
+    }
+
+    pub fn into_owned(self) -> OwnedEvent {
+        match self {
+            Event::Open { name, args, span } => OwnedEvent::Open { name: name.into(), args: args.into(), span },
+            Event::Close { name, span } => OwnedEvent::Close { name: name.into(), span },
+            Event::SelfClosing { name, args, span } => OwnedEvent::SelfClosing { name: name.into(), args: args.into(), span },
+            Event::Branch { name, args, span } => OwnedEvent::Branch { name: name.into(), args: args.into(), span },
+            Event::Text(span) => OwnedEvent::Text(span),
+            Event::Unmatched(span) => OwnedEvent::Unmatched(span),
+        }
+    }
+}
+
+impl OwnedEvent {
+    pub fn span(&self) -> Range<usize> {
+        self.as_event().span()
+    }
+
+    // Borrows the event, e.g. to match on it with string patterns.
+    pub fn as_event(&self) -> Event<'_> {
+        match self {
+            OwnedEvent::Open { name, args, span } => Event::Open { name, args, span: span.clone() },
+            OwnedEvent::Close { name, span } => Event::Close { name, span: span.clone() },
+            OwnedEvent::SelfClosing { name, args, span } => Event::SelfClosing { name, args, span: span.clone() },
+            OwnedEvent::Branch { name, args, span } => Event::Branch { name, args, span: span.clone() },
+            OwnedEvent::Text(span) => Event::Text(span.clone()),
+            OwnedEvent::Unmatched(span) => Event::Unmatched(span.clone()),
+        }
+    }
+
+    // Moves the span further into the input, e.g. past the chunks read before.
+    fn shift(&mut self, offset: usize) {
+        let span = match self {
+            OwnedEvent::Open { span, .. } |
+            OwnedEvent::Close { span, .. } |
+            OwnedEvent::SelfClosing { span, .. } |
+            OwnedEvent::Branch { span, .. } |
+            OwnedEvent::Text(span) |
+            OwnedEvent::Unmatched(span) => span,
+        };
+
+        span.start += offset;
+        span.end += offset;
+    }
+}
+
+// The events of an input which is all in memory.
+// The text between two tags is always a single event.
+pub struct Events<'a> {
+    cursor: Cursor<'a>,
+}
+
+impl<'a> Events<'a> {
+    pub fn new(input: &'a str) -> Events<'a> {
+        Events {
+            cursor: Cursor::new(input),
+        }
+    }
+}
+
+impl<'a> Iterator for Events<'a> {
+    type Item = Event<'a>;
+
+    fn next(&mut self) -> Option<Event<'a>> {
+        scan(&mut self.cursor, true)
+    }
+}
+
+// The events of an input which is read a chunk at a time, e.g. from a large file,
+// with the spans counted from the start of the input. Only the text of the tag
+// being read is kept in memory, so the text between tags is only given as a span.
+// A tag which is never closed is kept until the end of the input, since only then
+// it turns out to be text, but the input after it is only searched once.
+pub struct ReadEvents<R> {
+    reader: R,
+    // The input which has been read, from the byte 'offset' of the whole input,
+    // and how much of it has been scanned
+    buffer: String,
+    offset: usize,
+    pos: usize,
+    // How far the buffer has been searched for the end of the tag at 'pos',
+    // so that every chunk only has to be searched once
+    searched: usize,
+    // The bytes of a char which is split between two chunks
+    partial: Vec<u8>,
+    // The text scanned so far, which the next chunk may continue
+    text: Option<Range<usize>>,
+    // The event after the text, which is returned after it
+    queued: Option<OwnedEvent>,
+    eof: bool,
+}
+
+impl<R: Read> ReadEvents<R> {
+    pub fn new(reader: R) -> ReadEvents<R> {
+        ReadEvents {
+            reader,
+            buffer: String::new(),
+            offset: 0,
+            pos: 0,
+            searched: 0,
+            partial: Vec::new(),
+            text: None,
+            queued: None,
+            eof: false,
+        }
+    }
+
+    // Reads the next chunk into the buffer, and drops what has been scanned from it.
+    fn fill(&mut self) -> io::Result<()> {
+        self.buffer.drain(..self.pos);
+        self.offset += self.pos;
+        self.searched -= self.pos.min(self.searched);
+        self.pos = 0;
+
+        let mut chunk = [0; CHUNK];
+
+        let n = loop {
+            match self.reader.read(&mut chunk) {
+                Ok(n) => break n,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        };
+
+        if n == 0 {
+            self.eof = true;
+
+            return match self.partial.is_empty() {
+                true => Ok(()),
+                false => Err(io::Error::new(io::ErrorKind::InvalidData, "stream ended in the middle of a char")),
+            };
+        }
+
+        self.partial.extend_from_slice(&chunk[..n]);
+
+        let valid = match str::from_utf8(&self.partial) {
+            Ok(s) => s.len(),
+            // The rest of the char is in the next chunk.
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(io::Error::new(io::ErrorKind::InvalidData, e)),
+        };
+
+        let s = str::from_utf8(&self.partial[..valid]).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+
+        self.buffer.push_str(s);
+        self.partial.drain(..valid);
+
+        Ok(())
+    }
+}
+
+impl<R> ReadEvents<R> {
+    // Nothing more is returned after an error.
+    fn fail(&mut self, e: io::Error) -> io::Result<OwnedEvent> {
+        self.eof = true;
+        self.buffer.clear();
+        self.pos = 0;
+        self.searched = 0;
+        self.text = None;
+
+        Err(e)
+    }
+}
+
+// The byte offset of the last char of a string, or 0 if it is empty.
+fn last_char(s: &str) -> usize {
+    s.char_indices().next_back().map_or(0, |(i, _)| i)
+}
+
+impl<R: Read> Iterator for ReadEvents<R> {
+    type Item = io::Result<OwnedEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.queued.take() {
+            return Some(Ok(event));
+        }
+
+        loop {
+            let mut cursor = Cursor::new(&self.buffer);
+
+            // The tag at 'pos' can't be finished without another sign.
+            if !self.eof && self.searched > self.pos {
+                cursor.seek(self.searched);
+
+                if find_sign(&mut cursor).is_none() {
+                    self.searched = last_char(&self.buffer).max(self.searched);
+
+                    if let Err(e) = self.fill() {
+                        return Some(self.fail(e));
+                    }
+
+                    continue;
+                }
+            }
+
+            cursor.seek(self.pos);
+
+            match scan(&mut cursor, self.eof) {
+                Some(Event::Text(span)) => {
+                    self.pos = cursor.byte_offset();
+
+                    let span = self.offset + span.start..self.offset + span.end;
+
+                    self.text = Some(match self.text.take() {
+                        Some(text) => text.start..span.end,
+                        None => span,
+                    });
+                },
+                Some(event) => {
+                    self.pos = cursor.byte_offset();
+
+                    let mut event = event.into_owned();
+                    event.shift(self.offset);
+
+                    return match self.text.take() {
+                        Some(text) => {
+                            self.queued = Some(event);
+                            Some(Ok(OwnedEvent::Text(text)))
+                        },
+                        None => Some(Ok(event)),
+                    };
+                },
+                None if self.eof => return self.text.take().map(|text| Ok(OwnedEvent::Text(text))),
+                None => {
+                    // Search the rest of a tag from the last char on, which could start its sign.
+                    if self.buffer[self.pos..].starts_with("<<") || self.buffer[self.pos..].starts_with(">>") {
+                        self.searched = last_char(&self.buffer).max(self.pos + 2);
+                    }
+
+                    if let Err(e) = self.fill() {
+                        return Some(self.fail(e));
+                    }
+                },
+            }
+        }
+    }
+}
+
+// Puts the cursor after the next event and returns it.
+// Unless the input is complete, returns None rather than an event which
+// more input could change, e.g. a tag which isn't finished yet.
+fn scan<'a>(cursor: &mut Cursor<'a>, complete: bool) -> Option<Event<'a>> {
+    let start = cursor.byte_offset();
+
+    let mut c = cursor.clone();
+
+    let first = find_sign(&mut c);
+
+    let end = match first {
+        Some(_) => c.byte_offset(),
+        None if complete => c.byte_offset(),
+        // The last char could be the start of a sign.
+        None => cursor.input()[..c.byte_offset()].char_indices().next_back().map_or(start, |(i, _)| i.max(start)),
+    };
+
+    if end > start {
+        cursor.seek(end);
+
+        return Some(Event::Text(start..end));
+    }
+
+    let first = first?;
+
+    c.skip(2);
+
+    let name = parse_tag_name(&mut c);
+
+    let args_start = c.byte_offset();
+
+    let second = match find_sign(&mut c) {
+        Some(sign) => sign,
+        None if complete => {
+            // Keep the sign as text.
+            cursor.skip(2);
+
+            return Some(Event::Unmatched(start..cursor.byte_offset()));
+        },
+        None => return None,
+    };
+
+    let args = c.input()[args_start..c.byte_offset()].trim();
+
+    c.skip(2);
+
+    let span = start..c.byte_offset();
+
+    *cursor = c;
+
+    Some(match (first, second) {
+        (Sign::Opening, Sign::Opening) => Event::Open { name, args, span },
+        (Sign::Opening, Sign::Closing) => Event::SelfClosing { name, args, span },
+        (Sign::Closing, Sign::Closing) => Event::Close { name, span },
+        (Sign::Closing, Sign::Opening) => Event::Branch { name, args, span },
+    })
+}
+
+fn parse_tag_name<'a>(cursor: &mut Cursor<'a>) -> &'a str {
+    cursor.skip_while(|c| c.is_ascii_whitespace());
+
+    // Dots are used to access fields of variables, e.g. 'page.title'.
+    cursor.take_while(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Sign {
+    Opening,
+    Closing,
+}
+
+// Puts the cursor right before the next sign and returns the type of sign.
+// The signs are ASCII, so the bytes are searched rather than the chars, and
+// a match is always at the start of a char.
+fn find_sign(cursor: &mut Cursor) -> Option<Sign> {
+    let bytes = cursor.input().as_bytes();
+    let mut i = cursor.byte_offset();
+
+    while i + 1 < bytes.len() {
+        match bytes[i + 1] {
+            b @ b'<' | b @ b'>' => {
+                if bytes[i] == b {
+                    cursor.seek(i);
+
+                    return Some(if b == b'<' { Sign::Opening } else { Sign::Closing });
+                }
+
+                i += 1;
+            },
+            // Neither byte can start a sign.
+            _ => i += 2,
+        }
+    }
+
+    cursor.seek(bytes.len());
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn no_signs() {
+        let mut cursor = Cursor::new("abc");
+
+        let sign = find_sign(&mut cursor);
+
+        // When there are no signs to be found,
+        // the cursor should be at the end of the input
+        // and the returned value should be None.
+        assert!(sign.is_none());
+        assert_eq!(cursor.first(), None);
+    }
+
+    #[test]
+    fn only_one_opening_sign() {
+        let mut cursor = Cursor::new("<<");
+
+        let sign = find_sign(&mut cursor);
+
+        assert_eq!(sign, Some(Sign::Opening));
+        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
+        assert_eq!(cursor.input().chars().count() - cursor.position(), 2);
+    }
+
+    #[test]
+    fn only_two_opening_signs() {
+        let mut cursor = Cursor::new("<<<<");
+
+        let sign = find_sign(&mut cursor);
+
+        assert_eq!(sign, Some(Sign::Opening));
+        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
+        assert_eq!(cursor.position(), 0);
+
+        cursor.skip(2);
+
+        let sign = find_sign(&mut cursor);
+
+        assert_eq!(sign, Some(Sign::Opening));
+        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
+        assert_eq!(cursor.position(), 2);
+    }
+
+    #[test]
+    fn one_opening_sign() {
+        let mut cursor = Cursor::new("abc<<def");
+
+        let sign = find_sign(&mut cursor);
+
+        assert_eq!(sign, Some(Sign::Opening));
+        assert_eq!((cursor.first(), cursor.second()), (Some('<'), Some('<')));
+        assert_eq!(cursor.position(), 3);
+    }
+
+    #[test]
+    fn sign_after_multibyte_chars() {
+        let mut cursor = Cursor::new("å<ä>ö>>");
+
+        let sign = find_sign(&mut cursor);
+
+        assert_eq!(sign, Some(Sign::Closing));
+        assert_eq!(cursor.position(), 5);
+        assert_eq!(cursor.byte_offset(), 8);
+    }
+
+    #[test]
+    fn sign_without_tag_at_the_end() {
+        let events: Vec<Event> = Events::new("<<abc<<def").collect();
+
+        assert_eq!(events, vec![
+            Event::Open { name: "abc", args: "", span: 0..7 },
+            Event::Text(7..10),
+        ]);
+    }
+
+    #[test]
+    fn events_of_tags() {
+        let input = "a<< if b <<<<c>>>>else<<d>>if>>e<<";
+
+        let events: Vec<Event> = Events::new(input).collect();
+
+        assert_eq!(events, vec![
+            Event::Text(0..1),
+            Event::Open { name: "if", args: "b", span: 1..11 },
+            Event::SelfClosing { name: "c", args: "", span: 11..16 },
+            Event::Branch { name: "else", args: "", span: 16..24 },
+            Event::Text(24..25),
+            Event::Close { name: "if", span: 25..31 },
+            Event::Text(31..32),
+            Event::Unmatched(32..34),
+        ]);
+
+        // The events cover the whole input.
+        let spans: Vec<&str> = events.iter().map(|e| &input[e.span()]).collect();
+        assert_eq!(spans.concat(), input);
+    }
+
+    // Reads a byte at a time, to split the input in as many places as possible.
+    struct Bytes<'a>(&'a [u8]);
+
+    impl Read for Bytes<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.split_first() {
+                Some((b, rest)) if !buf.is_empty() => {
+                    buf[0] = *b;
+                    self.0 = rest;
+                    Ok(1)
+                },
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn reads_the_same_events_in_chunks() {
+        let inputs = [
+            "a<< if b <<<<c>>>>else<<d>>if>>e<<",
+            "åäö <p>x</p> <<ü<<日本>>ü>> >",
+            "<<<<>>>><<",
+            "",
+        ];
+
+        for input in &inputs {
+            let expected: Vec<OwnedEvent> = Events::new(input).map(Event::into_owned).collect();
+
+            let read: Vec<OwnedEvent> = ReadEvents::new(Bytes(input.as_bytes())).map(Result::unwrap).collect();
+            assert_eq!(read, expected, "{}", input);
+
+            let read: Vec<OwnedEvent> = ReadEvents::new(input.as_bytes()).map(Result::unwrap).collect();
+            assert_eq!(read, expected, "{}", input);
+        }
+    }
+
+    #[test]
+    fn reads_large_inputs() {
+        let input = "<li><<page.title>></li>\n".repeat(2 * CHUNK);
+
+        let events = ReadEvents::new(input.as_bytes()).map(Result::unwrap);
+
+        assert!(events.eq(Events::new(&input).map(Event::into_owned)));
+    }
+
+    #[test]
+    fn reads_long_tags() {
+        let args = "ä ".repeat(2 * CHUNK);
+        let inputs = [
+            format!("a<<x {}>>b", args),
+            format!("a<<x {}<<b>>x>>", args),
+            format!("a<<x {}", args),
+            format!("a>>x {}>", args),
+        ];
+
+        for input in &inputs {
+            let expected: Vec<OwnedEvent> = Events::new(input).map(Event::into_owned).collect();
+
+            let read: Vec<OwnedEvent> = ReadEvents::new(input.as_bytes()).map(Result::unwrap).collect();
+            assert_eq!(read, expected);
+        }
+    }
+
+    #[test]
+    fn fails_on_invalid_utf8() {
+        let mut events = ReadEvents::new(Bytes(b"<<a>>\xff<<b>>"));
+
+        assert!(matches!(events.next(), Some(Ok(OwnedEvent::SelfClosing { .. }))));
+        assert_eq!(events.next().unwrap().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert!(events.next().is_none());
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::DateTime;
+use chrono::FixedOffset;
+
+use crate::context::Value;
+use crate::filters::escape;
+
+// What the entries of a feed contain.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FeedContent {
+    // The 'summary' in the front matter of the page
+    Summary,
+    // The content of the page
+    Full,
+}
+
+#[derive(Debug)]
+pub struct Feed {
+    pub title: String,
+    // The absolute url of the page the feed belongs to
+    pub url: String,
+    // Newest first
+    pub entries: Vec<Entry>,
+}
+
+#[derive(Debug)]
+pub struct Entry {
+    pub title: String,
+    // The absolute url of the page
+    pub url: String,
+    pub date: Option<DateTime<FixedOffset>>,
+    // HTML, which is escaped when written to the feed
+    pub content: String,
+}
+
+impl Feed {
+    // Sorts the entries with the newest first, and those without a date last.
+    pub fn sort(&mut self) {
+        self.entries.sort_by_key(|e| std::cmp::Reverse(e.date));
+    }
+
+    // The date of the newest entry, or the given date if no entry has one.
+    pub fn updated(&self, default: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+        self.entries.iter().filter_map(|e| e.date).max().unwrap_or(default)
+    }
+
+    // RSS 2.0, see https://www.rssboard.org/rss-specification
+    pub fn rss(&self, feed_url: &str, updated: DateTime<FixedOffset>) -> String {
+        let mut out = String::new();
+
+        out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        out.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+        out.push_str("<channel>\n");
+        writeln!(out, "<title>{}</title>", escape(&self.title)).unwrap();
+        writeln!(out, "<link>{}</link>", escape(&self.url)).unwrap();
+        writeln!(out, "<description>{}</description>", escape(&self.title)).unwrap();
+        writeln!(out, "<atom:link href=\"{}\" rel=\"self\" type=\"application/rss+xml\"/>", escape(feed_url)).unwrap();
+        writeln!(out, "<lastBuildDate>{}</lastBuildDate>", self.updated(updated).to_rfc2822()).unwrap();
+
+        for e in &self.entries {
+            out.push_str("<item>\n");
+            writeln!(out, "<title>{}</title>", escape(&e.title)).unwrap();
+            writeln!(out, "<link>{}</link>", escape(&e.url)).unwrap();
+            writeln!(out, "<guid>{}</guid>", escape(&e.url)).unwrap();
+
+            if let Some(date) = e.date {
+                writeln!(out, "<pubDate>{}</pubDate>", date.to_rfc2822()).unwrap();
+            }
+
+            writeln!(out, "<description>{}</description>", escape(&e.content)).unwrap();
+            out.push_str("</item>\n");
+        }
+
+        out.push_str("</channel>\n");
+        out.push_str("</rss>\n");
+
+        out
+    }
+
+    // Atom, see RFC 4287
+    pub fn atom(&self, feed_url: &str, updated: DateTime<FixedOffset>, content: FeedContent) -> String {
+        let mut out = String::new();
+
+        let updated = self.updated(updated);
+
+        out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        writeln!(out, "<title>{}</title>", escape(&self.title)).unwrap();
+        writeln!(out, "<id>{}</id>", escape(feed_url)).unwrap();
+        writeln!(out, "<link href=\"{}\"/>", escape(&self.url)).unwrap();
+        writeln!(out, "<link href=\"{}\" rel=\"self\"/>", escape(feed_url)).unwrap();
+        writeln!(out, "<updated>{}</updated>", updated.to_rfc3339()).unwrap();
+
+        let element = match content {
+            FeedContent::Summary => "summary",
+            FeedContent::Full => "content",
+        };
+
+        for e in &self.entries {
+            out.push_str("<entry>\n");
+            writeln!(out, "<title>{}</title>", escape(&e.title)).unwrap();
+            writeln!(out, "<id>{}</id>", escape(&e.url)).unwrap();
+            writeln!(out, "<link href=\"{}\"/>", escape(&e.url)).unwrap();
+            // Atom requires every entry to have a date.
+            writeln!(out, "<updated>{}</updated>", e.date.unwrap_or(updated).to_rfc3339()).unwrap();
+            writeln!(out, "<{} type=\"html\">{}</{}>", element, escape(&e.content), element).unwrap();
+            out.push_str("</entry>\n");
+        }
+
+        out.push_str("</feed>\n");
+
+        out
+    }
+
+    // The 'feed' variable, for feeds rendered with a template.
+    pub fn to_value(&self, feed_url: &str, updated: DateTime<FixedOffset>) -> Value {
+        let updated = self.updated(updated);
+
+        let entries = self.entries.iter().map(|e| {
+            let mut m = BTreeMap::new();
+            m.insert("title".to_owned(), Value::String(e.title.clone()));
+            m.insert("url".to_owned(), Value::String(e.url.clone()));
+            m.insert("content".to_owned(), Value::String(e.content.clone()));
+
+            let date = e.date.unwrap_or(updated);
+            m.insert("rfc3339".to_owned(), Value::String(date.to_rfc3339()));
+            m.insert("rfc822".to_owned(), Value::String(date.to_rfc2822()));
+
+            Value::Map(m)
+        }).collect();
+
+        let mut m = BTreeMap::new();
+        m.insert("title".to_owned(), Value::String(self.title.clone()));
+        m.insert("url".to_owned(), Value::String(self.url.clone()));
+        m.insert("feed_url".to_owned(), Value::String(feed_url.to_owned()));
+        m.insert("rfc3339".to_owned(), Value::String(updated.to_rfc3339()));
+        m.insert("rfc822".to_owned(), Value::String(updated.to_rfc2822()));
+        m.insert("entries".to_owned(), Value::List(entries));
+
+        Value::Map(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::date;
+
+    fn feed() -> Feed {
+        let mut f = Feed {
+            title: "Posts & News".to_owned(),
+            url: "https://example.com/posts/".to_owned(),
+            entries: vec![
+                Entry {
+                    title: "Old".to_owned(),
+                    url: "https://example.com/posts/old.html".to_owned(),
+                    date: date::parse("2020-01-01"),
+                    content: "<p>Old</p>".to_owned(),
+                },
+                Entry {
+                    title: "New".to_owned(),
+                    url: "https://example.com/posts/new.html".to_owned(),
+                    date: date::parse("2020-10-01T12:00:00+02:00"),
+                    content: "<p>New</p>".to_owned(),
+                },
+            ],
+        };
+
+        f.sort();
+
+        f
+    }
+
+    #[test]
+    fn newest_first() {
+        let f = feed();
+
+        assert_eq!(f.entries[0].title, "New");
+    }
+
+    #[test]
+    fn rss_dates_and_escaping() {
+        let rss = feed().rss("https://example.com/posts/rss.xml", date::parse("2000-01-01").unwrap());
+
+        assert!(rss.contains("<title>Posts &amp; News</title>"));
+        assert!(rss.contains("<pubDate>Thu, 1 Oct 2020 12:00:00 +0200</pubDate>"));
+        assert!(rss.contains("<lastBuildDate>Thu, 1 Oct 2020 12:00:00 +0200</lastBuildDate>"));
+        assert!(rss.contains("<description>&lt;p&gt;New&lt;/p&gt;</description>"));
+    }
+
+    #[test]
+    fn atom_dates_and_content() {
+        let d = date::parse("2000-01-01").unwrap();
+
+        let atom = feed().atom("https://example.com/posts/atom.xml", d, FeedContent::Full);
+
+        assert!(atom.contains("<updated>2020-10-01T12:00:00+02:00</updated>"));
+        assert!(atom.contains("<content type=\"html\">&lt;p&gt;Old&lt;/p&gt;</content>"));
+
+        let atom = feed().atom("https://example.com/posts/atom.xml", d, FeedContent::Summary);
+
+        assert!(atom.contains("<summary type=\"html\">"));
+    }
+}
@@ -0,0 +1,441 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::iter::FromIterator;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+// Where a site is read from and built into, see 'Mold::set_file_system'.
+// Paths are the ones given to Mold, e.g. relative to the current directory.
+pub trait FileSystem {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    // Writes a file, replacing it if it exists. Its directory must exist.
+    fn write(&self, path: &Path, content: &[u8]) -> io::Result<()>;
+
+    // The files and directories in a directory, in any order.
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>>;
+
+    // Creates a directory, and fails if it already exists.
+    fn create_dir(&self, path: &Path) -> io::Result<()>;
+
+    // Creates a directory and the directories it is in, unless they exist.
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    // Moves a file or directory, which doesn't replace a directory that isn't empty.
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn exists(&self, path: &Path) -> bool;
+
+    fn is_file(&self, path: &Path) -> bool;
+
+    fn is_dir(&self, path: &Path) -> bool;
+
+    // When the file was last changed, if that is known.
+    fn modified(&self, path: &Path) -> Option<SystemTime>;
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DirEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+}
+
+// The file system of the computer.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DiskFs;
+
+impl FileSystem for DiskFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(path)
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        fs::read_to_string(path)
+    }
+
+    fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        fs::write(path, content)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let mut entries = Vec::new();
+
+        for f in path.read_dir()? {
+            let f = f?;
+
+            entries.push(DirEntry {
+                path: f.path(),
+                is_dir: f.file_type()?.is_dir(),
+            });
+        }
+
+        Ok(entries)
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        fs::canonicalize(path)
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        path.is_file()
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        path.is_dir()
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        path.metadata().and_then(|m| m.modified()).ok()
+    }
+}
+
+// A file system in memory, e.g. for building a site without touching the disk.
+// Clones share the same files, so that one can be given to Mold and the other
+// used to look at what it built.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFs {
+    // By path without '.' and '..', where the directory '' holds relative paths.
+    entries: Arc<Mutex<BTreeMap<PathBuf, Entry>>>,
+}
+
+#[derive(Debug, Clone)]
+enum Entry {
+    File(Vec<u8>, SystemTime),
+    Dir,
+}
+
+impl MemoryFs {
+    pub fn new() -> MemoryFs {
+        MemoryFs::default()
+    }
+
+    // Adds a file, and the directories it is in.
+    pub fn insert<P: AsRef<Path>, C: Into<Vec<u8>>>(&self, path: P, content: C) {
+        let path = normalize(path.as_ref());
+
+        let mut entries = self.entries.lock().unwrap();
+
+        for dir in path.ancestors().skip(1) {
+            entries.insert(dir.to_path_buf(), Entry::Dir);
+        }
+
+        entries.insert(path, Entry::File(content.into(), SystemTime::now()));
+    }
+
+    // The content of a file, if there is one at the path.
+    pub fn get<P: AsRef<Path>>(&self, path: P) -> Option<Vec<u8>> {
+        match self.entries.lock().unwrap().get(&normalize(path.as_ref())) {
+            Some(Entry::File(content, _)) => Some(content.clone()),
+            _ => None,
+        }
+    }
+
+    // The paths of all files, sorted.
+    pub fn files(&self) -> Vec<PathBuf> {
+        self.entries.lock().unwrap().iter()
+            .filter(|(_, e)| matches!(e, Entry::File(..)))
+            .map(|(path, _)| path.clone())
+            .collect()
+    }
+}
+
+impl<P: AsRef<Path>, C: Into<Vec<u8>>> FromIterator<(P, C)> for MemoryFs {
+    fn from_iter<I: IntoIterator<Item = (P, C)>>(files: I) -> MemoryFs {
+        let fs = MemoryFs::new();
+
+        for (path, content) in files {
+            fs.insert(path, content);
+        }
+
+        fs
+    }
+}
+
+impl FileSystem for MemoryFs {
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        match self.entries.lock().unwrap().get(&normalize(path)) {
+            Some(Entry::File(content, _)) => Ok(content.clone()),
+            Some(Entry::Dir) => Err(io::Error::other(format!("'{}' is a directory", path.display()))),
+            None => Err(not_found(path)),
+        }
+    }
+
+    fn write(&self, path: &Path, content: &[u8]) -> io::Result<()> {
+        let path = normalize(path);
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if !path.parent().is_none_or(|p| is_dir(&entries, p)) {
+            return Err(not_found(path.parent().unwrap()));
+        }
+
+        if let Some(Entry::Dir) = entries.get(&path) {
+            return Err(io::Error::other(format!("'{}' is a directory", path.display())));
+        }
+
+        entries.insert(path, Entry::File(content.to_vec(), SystemTime::now()));
+
+        Ok(())
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<DirEntry>> {
+        let dir = normalize(path);
+
+        let entries = self.entries.lock().unwrap();
+
+        if !is_dir(&entries, &dir) {
+            return Err(not_found(path));
+        }
+
+        Ok(entries.iter()
+            .filter(|(p, _)| p.parent() == Some(&dir) && !p.as_os_str().is_empty())
+            .map(|(p, e)| DirEntry {
+                // Keep the path as it was given, like 'std::fs::read_dir'.
+                path: path.join(p.file_name().unwrap()),
+                is_dir: matches!(e, Entry::Dir),
+            })
+            .collect())
+    }
+
+    fn create_dir(&self, path: &Path) -> io::Result<()> {
+        let dir = normalize(path);
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if is_dir(&entries, &dir) || entries.contains_key(&dir) {
+            return Err(io::Error::new(io::ErrorKind::AlreadyExists, format!("'{}' already exists", path.display())));
+        }
+
+        if !dir.parent().is_none_or(|p| is_dir(&entries, p)) {
+            return Err(not_found(path));
+        }
+
+        entries.insert(dir, Entry::Dir);
+
+        Ok(())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        let dir = normalize(path);
+
+        let mut entries = self.entries.lock().unwrap();
+
+        for d in dir.ancestors() {
+            match entries.get(d) {
+                Some(Entry::File(..)) => return Err(io::Error::other(format!("'{}' is a file", d.display()))),
+                Some(Entry::Dir) => break,
+                None => (),
+            }
+        }
+
+        for d in dir.ancestors() {
+            entries.insert(d.to_path_buf(), Entry::Dir);
+        }
+
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        let file = normalize(path);
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if !matches!(entries.get(&file), Some(Entry::File(..))) {
+            return Err(not_found(path));
+        }
+
+        entries.remove(&file);
+
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+        let dir = normalize(path);
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if !matches!(entries.get(&dir), Some(Entry::Dir)) {
+            return Err(not_found(path));
+        }
+
+        entries.retain(|p, _| !p.starts_with(&dir));
+
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let (old, new) = (normalize(from), normalize(to));
+
+        let mut entries = self.entries.lock().unwrap();
+
+        if !entries.contains_key(&old) {
+            return Err(not_found(from));
+        }
+
+        if !new.parent().is_none_or(|p| is_dir(&entries, p)) {
+            return Err(not_found(to));
+        }
+
+        if entries.keys().any(|p| p.starts_with(&new) && *p != new) {
+            return Err(io::Error::other(format!("'{}' is a directory which isn't empty", to.display())));
+        }
+
+        let moved: Vec<(PathBuf, Entry)> = entries.iter()
+            .filter(|(p, _)| p.starts_with(&old))
+            .map(|(p, e)| (new.join(p.strip_prefix(&old).unwrap()), e.clone()))
+            .collect();
+
+        entries.retain(|p, _| !p.starts_with(&old));
+        entries.remove(&new);
+        entries.extend(moved);
+
+        Ok(())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        let path = normalize(path);
+
+        match self.exists(&path) {
+            true => Ok(path),
+            false => Err(not_found(&path)),
+        }
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        let path = normalize(path);
+        let entries = self.entries.lock().unwrap();
+
+        is_dir(&entries, &path) || entries.contains_key(&path)
+    }
+
+    fn is_file(&self, path: &Path) -> bool {
+        matches!(self.entries.lock().unwrap().get(&normalize(path)), Some(Entry::File(..)))
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        is_dir(&self.entries.lock().unwrap(), &normalize(path))
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        match self.entries.lock().unwrap().get(&normalize(path)) {
+            Some(Entry::File(_, modified)) => Some(*modified),
+            _ => None,
+        }
+    }
+}
+
+// The roots, e.g. '' and '/', always exist.
+fn is_dir(entries: &BTreeMap<PathBuf, Entry>, path: &Path) -> bool {
+    path.parent().is_none() || matches!(entries.get(path), Some(Entry::Dir))
+}
+
+// Leaves out '.' and resolves '..', without looking at the files.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normal = PathBuf::new();
+
+    for c in path.components() {
+        match c {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                normal.pop();
+            },
+            c => normal.push(c),
+        }
+    }
+
+    normal
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("'{}' doesn't exist", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_in_memory() {
+        let fs: MemoryFs = vec![("templates/base.html", "<<content>>"), ("./content/posts/a.html", "a")].into_iter().collect();
+
+        assert_eq!(fs.read_to_string(Path::new("templates/../templates/base.html")).unwrap(), "<<content>>");
+        assert!(fs.is_dir(Path::new("content/posts")));
+        assert!(fs.is_dir(Path::new(".")));
+        assert_eq!(fs.read(Path::new("content/b.html")).unwrap_err().kind(), io::ErrorKind::NotFound);
+
+        let mut entries = fs.read_dir(Path::new("content")).unwrap();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(entries, vec![DirEntry { path: PathBuf::from("content/posts"), is_dir: true }]);
+
+        // The directory must exist.
+        assert!(fs.write(Path::new("build/index.html"), b"x").is_err());
+        fs.create_dir_all(Path::new("build")).unwrap();
+        fs.write(Path::new("build/index.html"), b"x").unwrap();
+        assert_eq!(fs.create_dir(Path::new("build")).unwrap_err().kind(), io::ErrorKind::AlreadyExists);
+
+        assert_eq!(fs.files(), vec![
+            PathBuf::from("build/index.html"),
+            PathBuf::from("content/posts/a.html"),
+            PathBuf::from("templates/base.html"),
+        ]);
+    }
+
+    #[test]
+    fn renames_and_removes_directories() {
+        let fs = MemoryFs::new();
+        fs.insert("stage/index.html", "new");
+        fs.insert("stage/posts/a.html", "a");
+        fs.insert("output/index.html", "old");
+
+        // Not onto a directory which isn't empty
+        assert!(fs.rename(Path::new("stage"), Path::new("output")).is_err());
+
+        fs.rename(Path::new("output"), Path::new("old")).unwrap();
+        fs.rename(Path::new("stage"), Path::new("output")).unwrap();
+        fs.remove_dir_all(Path::new("old")).unwrap();
+
+        assert_eq!(fs.get("output/index.html"), Some(b"new".to_vec()));
+        assert_eq!(fs.get("output/posts/a.html"), Some(b"a".to_vec()));
+        assert!(!fs.exists(Path::new("stage")));
+        assert!(!fs.exists(Path::new("old/index.html")));
+    }
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use chrono::format::Item;
+use chrono::format::StrftimeItems;
+
+use crate::context::Value;
+use crate::date;
+
+pub type Filter = Box<dyn Fn(&Value, &[Value]) -> Result<Value, String>>;
+
+// The filters which can be used in variable references, e.g. '<<page.title | upper>>'.
+pub struct Filters {
+    filters: HashMap<String, Filter>,
+}
+
+impl Filters {
+    // Creates the set of built-in filters.
+    pub fn new() -> Filters {
+        let mut f = Filters {
+            filters: HashMap::new(),
+        };
+
+        f.insert("upper", |v, _| Ok(Value::String(v.to_string().to_uppercase())));
+        f.insert("lower", |v, _| Ok(Value::String(v.to_string().to_lowercase())));
+        f.insert("date", date_filter);
+        f.insert("truncate", truncate);
+        f.insert("slugify", |v, _| Ok(Value::String(slugify(&v.to_string()))));
+        f.insert("urlencode", |v, _| Ok(Value::String(urlencode(&v.to_string()))));
+        f.insert("escape", |v, _| Ok(Value::String(escape(&v.to_string()))));
+        f.insert("json", |v, _| Ok(Value::String(json(v))));
+        f.insert("default", default);
+
+        f
+    }
+
+    pub fn insert<F>(&mut self, name: &str, filter: F) where
+        F: Fn(&Value, &[Value]) -> Result<Value, String> + 'static {
+        self.filters.insert(name.to_owned(), Box::new(filter));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Filter> {
+        self.filters.get(name)
+    }
+}
+
+impl Default for Filters {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Splits the arguments of a variable reference into its filters,
+// e.g. '| date "%Y" | upper' into [("date", ["%Y"]), ("upper", [])].
+// Returns None if the arguments don't start with a pipe or a quote isn't closed.
+pub fn parse_pipeline(args: &str) -> Option<Vec<(&str, Vec<Value>)>> {
+    let mut pipeline = Vec::new();
+
+    let mut rest = args.trim_start().strip_prefix('|')?;
+
+    loop {
+        rest = rest.trim_start();
+
+        let end = rest.find(|c: char| c.is_whitespace() || c == '|').unwrap_or(rest.len());
+        let name = &rest[..end];
+        rest = &rest[end..];
+
+        if name.is_empty() {
+            return None;
+        }
+
+        let mut args = Vec::new();
+
+        loop {
+            rest = rest.trim_start();
+
+            if rest.is_empty() {
+                pipeline.push((name, args));
+                return Some(pipeline);
+            }
+
+            if let Some(r) = rest.strip_prefix('|') {
+                rest = r;
+                break;
+            }
+
+            if let Some(r) = rest.strip_prefix('"') {
+                let end = r.find('"')?;
+                args.push(Value::String(r[..end].to_owned()));
+                rest = &r[end + 1..];
+            } else {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '|').unwrap_or(rest.len());
+                args.push(Value::String(rest[..end].to_owned()));
+                rest = &rest[end..];
+            }
+        }
+
+        pipeline.push((name, args));
+    }
+}
+
+// <<page.date | date "%Y-%m-%d">>
+fn date_filter(v: &Value, args: &[Value]) -> Result<Value, String> {
+    let format = match args {
+        [] => "%Y-%m-%d".to_owned(),
+        [format] => format.to_string(),
+        _ => return Err("expected a single format".to_owned()),
+    };
+
+    let d = date::parse(&v.to_string()).ok_or_else(|| format!("'{}' is not a date", v))?;
+
+    if StrftimeItems::new(&format).any(|i| i == Item::Error) {
+        return Err(format!("invalid format '{}'", format));
+    }
+
+    Ok(Value::String(d.format(&format).to_string()))
+}
+
+// <<page.summary | truncate 100 "...">>
+fn truncate(v: &Value, args: &[Value]) -> Result<Value, String> {
+    let (length, end) = match args {
+        [length] => (length, String::new()),
+        [length, end] => (length, end.to_string()),
+        _ => return Err("expected a length and an optional ending".to_owned()),
+    };
+
+    let length: usize = length.to_string().parse()
+        .map_err(|_| format!("'{}' is not a length", length))?;
+
+    let s = v.to_string();
+
+    if s.chars().count() <= length {
+        return Ok(Value::String(s));
+    }
+
+    let mut t: String = s.chars().take(length).collect();
+    t.push_str(&end);
+
+    Ok(Value::String(t))
+}
+
+// <<page.subtitle | default "None">>
+fn default(v: &Value, args: &[Value]) -> Result<Value, String> {
+    match args {
+        [d] => Ok(if v.is_truthy() { v.clone() } else { d.clone() }),
+        _ => Err("expected a single default value".to_owned()),
+    }
+}
+
+pub fn slugify(s: &str) -> String {
+    let mut slug = String::new();
+
+    for c in s.chars().flat_map(|c| c.to_lowercase()) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    while slug.ends_with('-') {
+        slug.pop();
+    }
+
+    slug
+}
+
+pub fn urlencode(s: &str) -> String {
+    let mut out = String::new();
+
+    for b in s.bytes() {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => out.push(b as char),
+            _ => write!(out, "%{:02X}", b).unwrap(),
+        }
+    }
+
+    out
+}
+
+pub fn escape(s: &str) -> String {
+    let mut out = String::new();
+
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+pub fn json(v: &Value) -> String {
+    match v {
+        Value::Bool(b) => b.to_string(),
+        Value::String(s) => json_string(s),
+        Value::List(l) => {
+            let items: Vec<String> = l.iter().map(json).collect();
+            format!("[{}]", items.join(","))
+        },
+        Value::Map(m) => {
+            let items: Vec<String> = m.iter()
+                .map(|(k, v)| format!("{}:{}", json_string(k), json(v)))
+                .collect();
+            format!("{{{}}}", items.join(","))
+        },
+    }
+}
+
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+
+    out.push('"');
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    fn apply(name: &str, v: Value, args: &[Value]) -> Result<Value, String> {
+        Filters::new().get(name).unwrap()(&v, args)
+    }
+
+    #[test]
+    fn parses_pipeline() {
+        let p = parse_pipeline("| date \"%d %B %Y\" | upper").unwrap();
+
+        assert_eq!(p, vec![("date", vec![s("%d %B %Y")]), ("upper", vec![])]);
+    }
+
+    #[test]
+    fn pipeline_must_start_with_pipe() {
+        assert_eq!(parse_pipeline("upper"), None);
+        assert_eq!(parse_pipeline("| default \"x"), None);
+        assert_eq!(parse_pipeline("| |"), None);
+    }
+
+    #[test]
+    fn date_formatting() {
+        let d = apply("date", s("2020-10-01"), &[s("%d/%m %Y")]);
+
+        assert_eq!(d, Ok(s("01/10 2020")));
+        assert!(apply("date", s("soon"), &[]).is_err());
+    }
+
+    #[test]
+    fn truncation() {
+        assert_eq!(apply("truncate", s("åäöabc"), &[s("3"), s("...")]), Ok(s("åäö...")));
+        assert_eq!(apply("truncate", s("abc"), &[s("3"), s("...")]), Ok(s("abc")));
+        assert!(apply("truncate", s("abc"), &[s("x")]).is_err());
+    }
+
+    #[test]
+    fn default_for_empty_values() {
+        assert_eq!(apply("default", s(""), &[s("x")]), Ok(s("x")));
+        assert_eq!(apply("default", s("a"), &[s("x")]), Ok(s("a")));
+    }
+
+    #[test]
+    fn slugs() {
+        assert_eq!(slugify("Hello, World!"), "hello-world");
+        assert_eq!(slugify("  Rust & Web  "), "rust-web");
+    }
+
+    #[test]
+    fn url_encoding() {
+        assert_eq!(urlencode("a b/å"), "a%20b%2F%C3%A5");
+    }
+
+    #[test]
+    fn html_escaping() {
+        assert_eq!(escape("<a href=\"x\">&</a>"), "&lt;a href=&quot;x&quot;&gt;&amp;&lt;/a&gt;");
+    }
+
+    #[test]
+    fn json_encoding() {
+        let v = Value::List(vec![s("a\"b"), Value::Bool(true)]);
+
+        assert_eq!(json(&v), "[\"a\\\"b\",true]");
+    }
+}
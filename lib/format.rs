@@ -0,0 +1,310 @@
+use std::fmt::Write;
+
+use crate::content_tree::Node;
+use crate::content_tree::Signature;
+use crate::syntax::SyntaxNode;
+use crate::syntax::SyntaxTag;
+use crate::syntax::SyntaxTree;
+use crate::templates;
+
+const INDENT: &str = "  ";
+
+// Text which whitespace at the start of its lines shows in, so it isn't indented.
+const PREFORMATTED: [&str; 2] = ["<pre", "<textarea"];
+
+// Formats a template, module or the body of a page: tags are spelled without
+// whitespace inside their signs, while empty blocks stay blocks, since a
+// self-closing tag doesn't fill in a block.
+// Content extending one of the templates has the blocks filling it in on lines
+// of their own, indented, since the text between them isn't rendered.
+// The lines of a tag's content spanning several lines are indented one step
+// deeper than the line of the tag, keeping their indentation relative to each
+// other, and the closing tag lines up with the tag. This only changes whitespace
+// at the start of lines, which HTML doesn't show, and content with '<pre' or
+// '<textarea' is left as it is. Any other text is kept as it is.
+pub fn format(tree: &SyntaxTree, templates: &[&str]) -> String {
+    let content = tree.to_content_tree();
+
+    let parent = templates::parent_tag(&content)
+        .filter(|tag| templates.contains(&tag.name) && tag.branches.is_empty())
+        .filter(|tag| tag.content.tags().next().is_some())
+        .filter(|tag| tag.content.children.iter().all(|n| match n {
+            Node::Text(text) => text.trim().is_empty(),
+            Node::Tag(_) => true,
+        }));
+
+    let (parent, blocks) = match (parent, tree.children.first()) {
+        (Some(_), Some(SyntaxNode::Tag(tag @ SyntaxTag { content: Some(content), .. }))) => (tag, content),
+        _ => {
+            let mut out = String::new();
+            write_nodes(&mut out, &tree.children);
+            return out;
+        },
+    };
+
+    let mut out = String::new();
+
+    writeln!(out, "<<{}<<", Signature(parent.name, parent.args)).unwrap();
+
+    for node in &blocks.children {
+        if let SyntaxNode::Tag(tag) = node {
+            out.push_str(INDENT);
+            write_tag(&mut out, tag);
+            out.push('\n');
+        }
+    }
+
+    write!(out, ">>{}>>", parent.name).unwrap();
+
+    // Keep the whitespace at the end, e.g. the newline at the end of the file.
+    for node in &tree.children[1..] {
+        if let SyntaxNode::Text(text) = node {
+            out.push_str(text);
+        }
+    }
+
+    out
+}
+
+fn write_nodes(out: &mut String, nodes: &[SyntaxNode]) {
+    for node in nodes {
+        match node {
+            SyntaxNode::Tag(tag) => write_tag(out, tag),
+            SyntaxNode::Text(text) | SyntaxNode::Invalid(text) => out.push_str(text),
+        }
+    }
+}
+
+fn write_tag(out: &mut String, tag: &SyntaxTag) {
+    let content = match &tag.content {
+        Some(content) => content,
+        None => {
+            write!(out, "<<{}>>", Signature(tag.name, tag.args)).unwrap();
+            return;
+        },
+    };
+
+    // The indentation of the line which the tag is on
+    let line = &out[out.rfind('\n').map_or(0, |i| i + 1)..];
+    let indent = line[..line.len() - line.trim_start_matches([' ', '\t']).len()].to_owned();
+
+    write!(out, "<<{}<<", Signature(tag.name, tag.args)).unwrap();
+    write_content(out, content, &indent);
+
+    for branch in &tag.branches {
+        write!(out, ">>{}<<", Signature(branch.name, branch.args)).unwrap();
+        write_content(out, &branch.content, &indent);
+    }
+
+    write!(out, ">>{}>>", tag.name).unwrap();
+}
+
+// Writes the content of a tag or branch on a line indented by 'indent'.
+fn write_content(out: &mut String, content: &SyntaxTree, indent: &str) {
+    let multiline = content.children.iter().any(|n| matches!(n, SyntaxNode::Text(text) if text.contains('\n')));
+
+    if !multiline || is_preformatted(content) {
+        write_nodes(out, &content.children);
+        return;
+    }
+
+    let lines = line_starts(content);
+
+    // The indentation which all lines have in common is replaced.
+    let common = lines.iter()
+        .filter(|l| **l != LineStart::Closing && **l != LineStart::Blank)
+        .map(|l| match l {
+            LineStart::Indented(n) => *n,
+            _ => 0,
+        })
+        .min()
+        .unwrap_or(0);
+
+    let mut lines = lines.into_iter();
+
+    for node in &content.children {
+        let text = match node {
+            SyntaxNode::Tag(tag) => {
+                write_tag(out, tag);
+                continue;
+            },
+            SyntaxNode::Text(text) | SyntaxNode::Invalid(text) => text,
+        };
+
+        let mut parts = text.split('\n');
+        out.push_str(parts.next().unwrap_or_default());
+
+        for part in parts {
+            out.push('\n');
+
+            let rest = part.trim_start_matches([' ', '\t']);
+
+            match lines.next() {
+                Some(LineStart::Closing) => out.push_str(indent),
+                Some(LineStart::Blank) => (),
+                _ => {
+                    out.push_str(indent);
+                    out.push_str(INDENT);
+                    out.extend(part[..part.len() - rest.len()].chars().skip(common));
+                },
+            }
+
+            out.push_str(rest);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LineStart {
+    // A line with text or a tag after this many spaces or tabs
+    Indented(usize),
+    // A line with nothing but whitespace
+    Blank,
+    // The line of the closing tag or the next branch
+    Closing,
+}
+
+// The lines of a content, except for the first, which goes on after the opening tag.
+fn line_starts(content: &SyntaxTree) -> Vec<LineStart> {
+    let mut lines = Vec::new();
+
+    for (i, node) in content.children.iter().enumerate() {
+        let text = match node {
+            SyntaxNode::Text(text) | SyntaxNode::Invalid(text) => text,
+            SyntaxNode::Tag(_) => continue,
+        };
+
+        let last_node = i + 1 == content.children.len();
+        let parts: Vec<&str> = text.split('\n').skip(1).collect();
+
+        for (j, part) in parts.iter().enumerate() {
+            let rest = part.trim_start_matches([' ', '\t']);
+            let last_part = j + 1 == parts.len();
+
+            lines.push(match rest.is_empty() {
+                true if last_part && last_node => LineStart::Closing,
+                // Whitespace before a tag
+                true if last_part => LineStart::Indented(part.len()),
+                true => LineStart::Blank,
+                false => LineStart::Indented(part.len() - rest.len()),
+            });
+        }
+    }
+
+    lines
+}
+
+fn is_preformatted(content: &SyntaxTree) -> bool {
+    content.children.iter().any(|n| match n {
+        SyntaxNode::Text(text) | SyntaxNode::Invalid(text) => PREFORMATTED.iter().any(|p| text.contains(p)),
+        SyntaxNode::Tag(tag) => {
+            tag.content.as_ref().is_some_and(is_preformatted) || tag.branches.iter().any(|b| is_preformatted(&b.content))
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt(input: &str, templates: &[&str]) -> String {
+        let (tree, errors) = SyntaxTree::parse(input);
+        assert_eq!(errors, []);
+
+        format(&tree, templates)
+    }
+
+    #[test]
+    fn normalises_tags() {
+        assert_eq!(fmt("a<< page.title >>b", &[]), "a<<page.title>>b");
+        assert_eq!(fmt("<< x >>", &[]), "<<x>>");
+        assert_eq!(fmt("<< if  a <<\n  b\n>> else <<c>> if >>\n", &[]), "<<if a<<\n  b\n>>else<<c>>if>>\n");
+        assert_eq!(fmt("<<p | truncate 3  \"a  b\" >>", &[]), "<<p | truncate 3  \"a  b\">>");
+    }
+
+    #[test]
+    fn indents_blocks_of_extending_content() {
+        let input = "<< base<<<<title<<Home>>title>>   <<content<<\n<p>Hi</p>\n>>content>>>>base>>\n";
+
+        assert_eq!(fmt(input, &["base"]), "\
+<<base<<
+  <<title<<Home>>title>>
+  <<content<<
+    <p>Hi</p>
+  >>content>>
+>>base>>
+");
+    }
+
+    #[test]
+    fn keeps_empty_blocks() {
+        let input = "<<base<<<< title<<>>title>><<content>>>>base>>";
+
+        assert_eq!(fmt(input, &["base"]), "<<base<<\n  <<title<<>>title>>\n  <<content>>\n>>base>>");
+        assert_eq!(fmt("<< x<<>> x>>", &[]), "<<x<<>>x>>");
+    }
+
+    #[test]
+    fn indents_nested_contents() {
+        let input = "\
+<ul>
+  <<for p in posts<<
+      <li>
+        <<if p.draft<<
+   Draft
+    >>else<<
+<<p.title>>
+        >>if>>
+      </li>
+
+  >>for>>
+</ul>
+";
+
+        assert_eq!(fmt(input, &[]), "\
+<ul>
+  <<for p in posts<<
+    <li>
+      <<if p.draft<<
+        Draft
+      >>else<<
+        <<p.title>>
+      >>if>>
+    </li>
+
+  >>for>>
+</ul>
+");
+        assert_eq!(fmt("<<if a<<b>>if>>", &[]), "<<if a<<b>>if>>");
+    }
+
+    #[test]
+    fn keeps_preformatted_text() {
+        let input = "<<if a<<\n<pre>\n  x\n</pre>\n>>if>>";
+
+        assert_eq!(fmt(input, &[]), input);
+    }
+
+    #[test]
+    fn keeps_text_unless_extending() {
+        let input = "<<quote<<<<b<<x>>b>> <<c>>>>quote>>\n";
+
+        assert_eq!(fmt(input, &["base"]), input);
+        assert_eq!(fmt("<<base<<x<<content>>>>base>>", &["base"]), "<<base<<x<<content>>>>base>>");
+    }
+
+    #[test]
+    fn is_idempotent() {
+        let inputs = [
+            "<< base<<<<title<<Home>>title>>\n<<content<<a>>content>>>>base>>\n",
+            "<<for p in posts<<<< p.title >>>>for>>",
+            "<<if a<<\n\t<<for b in c<<\n x\n\t  y>>for>>\n>>if>>\n",
+        ];
+
+        for input in &inputs {
+            let once = fmt(input, &["base"]);
+
+            assert_eq!(fmt(&once, &["base"]), once);
+        }
+    }
+}
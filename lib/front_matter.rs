@@ -0,0 +1,165 @@
+use std::collections::BTreeMap;
+
+use crate::context::Value;
+use crate::error::FrontMatterError;
+
+const DELIMITER: &str = "---";
+
+// Splits a content file into its front matter and the remaining body.
+// The front matter is a block of 'key: value' lines at the very start of the file,
+// surrounded by '---' lines. Values are strings, booleans or lists, e.g.
+//
+// ---
+// title: Hello
+// draft: true
+// tags: [rust, web]
+// ---
+pub fn split(input: &str) -> Result<(BTreeMap<String, Value>, &str), FrontMatterError> {
+    let rest = match strip_line(input, DELIMITER) {
+        Some(rest) => rest,
+        None => return Ok((BTreeMap::new(), input)),
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == DELIMITER {
+            // The first line is the opening delimiter.
+            let fields = parse(&rest[..offset], 2)?;
+
+            return Ok((fields, &rest[offset + line.len()..]));
+        }
+
+        offset += line.len();
+    }
+
+    Err(FrontMatterError::Unclosed)
+}
+
+// Parses 'key: value' lines, where 'first_line' is the line number of the first line.
+pub fn parse(input: &str, first_line: usize) -> Result<BTreeMap<String, Value>, FrontMatterError> {
+    let mut fields = BTreeMap::new();
+
+    // The key of a list whose items are written on the following lines.
+    let mut list: Option<String> = None;
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line.trim_end();
+
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let lineno = i + first_line;
+
+        if let Some(item) = line.trim_start().strip_prefix("- ") {
+            let key = list.as_ref().ok_or(FrontMatterError::InvalidLine(lineno))?;
+
+            if let Some(Value::List(l)) = fields.get_mut(key) {
+                l.push(parse_scalar(item));
+            }
+
+            continue;
+        }
+
+        let (key, value) = line.split_once(':').ok_or(FrontMatterError::InvalidLine(lineno))?;
+        let key = key.trim();
+        let value = value.trim();
+
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            return Err(FrontMatterError::InvalidLine(lineno));
+        }
+
+        if value.is_empty() {
+            list = Some(key.to_owned());
+            fields.insert(key.to_owned(), Value::List(Vec::new()));
+        } else {
+            list = None;
+            fields.insert(key.to_owned(), parse_value(value));
+        }
+    }
+
+    Ok(fields)
+}
+
+fn strip_line<'a>(input: &'a str, line: &str) -> Option<&'a str> {
+    let rest = input.strip_prefix(line)?;
+
+    rest.strip_prefix("\r\n").or_else(|| rest.strip_prefix('\n'))
+}
+
+fn parse_value(value: &str) -> Value {
+    if let Some(inner) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        let items = inner.split(',')
+            .map(|i| i.trim())
+            .filter(|i| !i.is_empty())
+            .map(parse_scalar)
+            .collect();
+
+        return Value::List(items);
+    }
+
+    parse_scalar(value)
+}
+
+fn parse_scalar(value: &str) -> Value {
+    let value = value.trim();
+
+    match value {
+        "true" => Value::Bool(true),
+        "false" => Value::Bool(false),
+        _ => {
+            let unquoted = value.strip_prefix('"').and_then(|v| v.strip_suffix('"'))
+                .or_else(|| value.strip_prefix('\'').and_then(|v| v.strip_suffix('\'')))
+                .unwrap_or(value);
+
+            Value::String(unquoted.to_owned())
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn s(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    #[test]
+    fn no_front_matter() {
+        let (fields, body) = split("<<base>>").unwrap();
+
+        assert!(fields.is_empty());
+        assert_eq!(body, "<<base>>");
+    }
+
+    #[test]
+    fn scalars_and_lists() {
+        let input = "---\ntitle: \"Hello: World\"\ndraft: true\ntags: [rust, web]\ncategories:\n  - news\n  - misc\n---\nbody";
+
+        let (fields, body) = split(input).unwrap();
+
+        assert_eq!(body, "body");
+        assert_eq!(fields.get("title"), Some(&s("Hello: World")));
+        assert_eq!(fields.get("draft"), Some(&Value::Bool(true)));
+        assert_eq!(fields.get("tags"), Some(&Value::List(vec![s("rust"), s("web")])));
+        assert_eq!(fields.get("categories"), Some(&Value::List(vec![s("news"), s("misc")])));
+    }
+
+    #[test]
+    fn unclosed_front_matter() {
+        assert_eq!(split("---\ntitle: a\n"), Err(FrontMatterError::Unclosed));
+    }
+
+    #[test]
+    fn comments_are_ignored() {
+        let fields = parse("# The title\ntitle: a\n", 1).unwrap();
+
+        assert_eq!(fields.len(), 1);
+    }
+
+    #[test]
+    fn invalid_line() {
+        assert_eq!(split("---\ntitle: a\nnonsense\n---\n"), Err(FrontMatterError::InvalidLine(3)));
+    }
+}
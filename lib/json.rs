@@ -0,0 +1,300 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::cursor::Cursor;
+use crate::filters::json_string;
+
+// A JSON value, as read and written by the language server.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(BTreeMap<String, Json>),
+}
+
+impl Json {
+    // Builds an object from its fields, e.g. 'Json::object(vec![("id", Json::Number(1.0))])'.
+    pub fn object(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+    }
+
+    pub fn string<S: Into<String>>(s: S) -> Json {
+        Json::String(s.into())
+    }
+
+    // Returns the field of an object, or Null.
+    pub fn get(&self, key: &str) -> &Json {
+        match self {
+            Json::Object(o) => o.get(key).unwrap_or(&Json::Null),
+            _ => &Json::Null,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_usize(&self) -> Option<usize> {
+        match self {
+            Json::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Some(*n as usize),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(a) => Some(a),
+            _ => None,
+        }
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as f64)
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => write!(f, "{}", json_string(s)),
+            Json::Array(a) => {
+                write!(f, "[")?;
+
+                for (i, v) in a.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", v)?;
+                }
+
+                write!(f, "]")
+            },
+            Json::Object(o) => {
+                write!(f, "{{")?;
+
+                for (i, (k, v)) in o.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}:{}", json_string(k), v)?;
+                }
+
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+// Parses a JSON document, or returns None if it isn't valid.
+pub fn parse(input: &str) -> Option<Json> {
+    let cursor = &mut Cursor::new(input);
+
+    let value = parse_value(cursor)?;
+
+    skip_whitespace(cursor);
+
+    match cursor.first() {
+        None => Some(value),
+        Some(_) => None,
+    }
+}
+
+fn parse_value(cursor: &mut Cursor) -> Option<Json> {
+    skip_whitespace(cursor);
+
+    match cursor.first()? {
+        'n' => parse_literal(cursor, "null", Json::Null),
+        't' => parse_literal(cursor, "true", Json::Bool(true)),
+        'f' => parse_literal(cursor, "false", Json::Bool(false)),
+        '"' => parse_string(cursor).map(Json::String),
+        '[' => {
+            cursor.skip(1);
+
+            let mut items = Vec::new();
+
+            skip_whitespace(cursor);
+            if cursor.first() == Some(']') {
+                cursor.skip(1);
+                return Some(Json::Array(items));
+            }
+
+            loop {
+                items.push(parse_value(cursor)?);
+
+                skip_whitespace(cursor);
+                match cursor.first()? {
+                    ',' => cursor.skip(1),
+                    ']' => {
+                        cursor.skip(1);
+                        return Some(Json::Array(items));
+                    },
+                    _ => return None,
+                }
+            }
+        },
+        '{' => {
+            cursor.skip(1);
+
+            let mut fields = BTreeMap::new();
+
+            skip_whitespace(cursor);
+            if cursor.first() == Some('}') {
+                cursor.skip(1);
+                return Some(Json::Object(fields));
+            }
+
+            loop {
+                skip_whitespace(cursor);
+                let key = parse_string(cursor)?;
+
+                skip_whitespace(cursor);
+                if cursor.first()? != ':' {
+                    return None;
+                }
+                cursor.skip(1);
+
+                fields.insert(key, parse_value(cursor)?);
+
+                skip_whitespace(cursor);
+                match cursor.first()? {
+                    ',' => cursor.skip(1),
+                    '}' => {
+                        cursor.skip(1);
+                        return Some(Json::Object(fields));
+                    },
+                    _ => return None,
+                }
+            }
+        },
+        _ => {
+            let number = cursor.take_while(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+
+            number.parse().ok().map(Json::Number)
+        },
+    }
+}
+
+fn parse_literal(cursor: &mut Cursor, literal: &str, value: Json) -> Option<Json> {
+    let word = cursor.take_while(|c| c.is_ascii_alphabetic());
+
+    if word == literal {
+        Some(value)
+    } else {
+        None
+    }
+}
+
+// Parses a string, with the cursor right before its opening quote.
+fn parse_string(cursor: &mut Cursor) -> Option<String> {
+    if cursor.first()? != '"' {
+        return None;
+    }
+    cursor.skip(1);
+
+    let mut s = String::new();
+
+    loop {
+        let c = cursor.first()?;
+        cursor.skip(1);
+
+        match c {
+            '"' => return Some(s),
+            '\\' => {
+                let e = cursor.first()?;
+                cursor.skip(1);
+
+                match e {
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'b' => s.push('\u{8}'),
+                    'f' => s.push('\u{c}'),
+                    'u' => {
+                        let unit = parse_hex(cursor)?;
+
+                        // Characters outside of the BMP are written as surrogate pairs.
+                        let c = if (0xd800..0xdc00).contains(&unit) {
+                            if cursor.first()? != '\\' || cursor.second()? != 'u' {
+                                return None;
+                            }
+                            cursor.skip(2);
+
+                            let low = parse_hex(cursor)?;
+                            char::from_u32(0x10000 + ((unit - 0xd800) << 10) + low.checked_sub(0xdc00)?)
+                        } else {
+                            char::from_u32(unit)
+                        };
+
+                        s.push(c?);
+                    },
+                    c => s.push(c),
+                }
+            },
+            c => s.push(c),
+        }
+    }
+}
+
+fn parse_hex(cursor: &mut Cursor) -> Option<u32> {
+    let mut n = 0;
+
+    for _ in 0..4 {
+        n = n * 16 + cursor.first()?.to_digit(16)?;
+        cursor.skip(1);
+    }
+
+    Some(n)
+}
+
+fn skip_whitespace(cursor: &mut Cursor) {
+    cursor.skip_while(|c| c.is_ascii_whitespace());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_values() {
+        let json = parse(r#" {"id": 1, "params": {"text": "a\"\nå😀", "list": [true, false, null, -2.5e1]}} "#).unwrap();
+
+        assert_eq!(json.get("id").as_usize(), Some(1));
+        assert_eq!(json.get("params").get("text").as_str(), Some("a\"\nå😀"));
+        assert_eq!(json.get("params").get("list"), &Json::Array(vec![
+            Json::Bool(true),
+            Json::Bool(false),
+            Json::Null,
+            Json::Number(-25.0),
+        ]));
+        assert_eq!(json.get("missing"), &Json::Null);
+    }
+
+    #[test]
+    fn rejects_invalid_json() {
+        for input in &["", "{", "[1,]", "{\"a\" 1}", "tru", "\"abc", "1 2"] {
+            assert_eq!(parse(input), None, "{}", input);
+        }
+    }
+
+    #[test]
+    fn writes_values() {
+        let json = Json::object(vec![
+            ("id", Json::from(3)),
+            ("result", Json::Array(vec![Json::Null, Json::string("a\"b")])),
+        ]);
+
+        assert_eq!(json.to_string(), r#"{"id":3,"result":[null,"a\"b"]}"#);
+        assert_eq!(parse(&json.to_string()), Some(json));
+    }
+}
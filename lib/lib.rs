@@ -1,10 +1,45 @@
 mod mold;
+mod compile;
 mod content_tree;
 
 mod cursor;
 mod error;
+mod events;
+
+mod context;
+mod date;
+mod diagnostic;
+mod feeds;
+mod file_system;
+mod filters;
+mod format;
+mod front_matter;
+mod json;
+mod lsp;
+mod output;
+mod pages;
+mod plugin;
+mod render;
+mod sitemap;
+mod source;
+mod taxonomies;
+mod tags;
 
 mod templates;
 mod statics;
+mod syntax;
+mod walk;
 
-pub use crate::mold::Mold;
+pub use crate::mold::{Mold, Prepared};
+pub use crate::context::{Context, Value};
+pub use crate::diagnostic::{Diagnostic, Origin, Severity, Snippet};
+pub use crate::feeds::FeedContent;
+pub use crate::file_system::{FileSystem, DirEntry, DiskFs, MemoryFs};
+pub use crate::pages::Page;
+pub use crate::plugin::Plugin;
+pub use crate::error::{Error, ParseError, FrontMatterError, RenderError};
+pub use crate::content_tree::{ContentTree, Node, Tag, Branch, SyntaxError};
+pub use crate::content_tree::{OwnedContentTree, OwnedNode, OwnedTag, OwnedBranch};
+pub use crate::events::{Event, Events, OwnedEvent, ReadEvents};
+pub use crate::syntax::{SyntaxTree, SyntaxNode, SyntaxTag, SyntaxBranch};
+pub use crate::walk::{NodePath, Step, VisitMut, Walk};
@@ -0,0 +1,766 @@
+use std::collections::HashMap;
+use std::env;
+use std::io;
+use std::io::BufRead;
+use std::io::Read;
+use std::io::Write;
+use std::ops::Range;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Origin;
+use crate::diagnostic;
+use crate::file_system::FileSystem;
+use crate::filters::urlencode;
+use crate::front_matter;
+use crate::json;
+use crate::json::Json;
+use crate::syntax::SyntaxNode;
+use crate::syntax::SyntaxTag;
+use crate::syntax::SyntaxTree;
+use crate::templates;
+
+// JSON-RPC error codes
+const PARSE_ERROR: i32 = -32700;
+const METHOD_NOT_FOUND: i32 = -32601;
+
+// The kinds of symbols and completions, as numbered by the protocol.
+const SYMBOL_NAMESPACE: usize = 3;
+const COMPLETION_PROPERTY: usize = 10;
+
+// A language server for the templates, modules and pages of a project, see 'serve'.
+pub struct Server<'f> {
+    // Where files which aren't open in the editor are read from
+    fs: &'f dyn FileSystem,
+    templates: Vec<PathBuf>,
+    modules: Vec<PathBuf>,
+    content: Vec<PathBuf>,
+    // The text of the documents open in the editor, which may not have been saved.
+    documents: HashMap<PathBuf, String>,
+    // Notifications to send once the current message has been handled
+    outgoing: Vec<Json>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Kind {
+    Template,
+    Module,
+    Page,
+}
+
+// A document parsed for answering a request.
+struct Document<'t> {
+    text: &'t str,
+    kind: Kind,
+    // Where the body starts, after the front matter of a page
+    body: Origin,
+    tree: Option<SyntaxTree<'t>>,
+}
+
+impl<'f> Server<'f> {
+    pub fn new(fs: &'f dyn FileSystem, templates: &[PathBuf], modules: &[PathBuf], content: &[PathBuf]) -> Server<'f> {
+        let absolute = |dirs: &[PathBuf]| dirs.iter().map(|d| absolute(fs, d)).collect();
+
+        Server {
+            fs,
+            templates: absolute(templates),
+            modules: absolute(modules),
+            content: absolute(content),
+            documents: HashMap::new(),
+            outgoing: Vec::new(),
+        }
+    }
+
+    // Handles a request or notification, and returns the result or an error code and message.
+    // The result of a notification is ignored.
+    fn handle(&mut self, method: &str, params: &Json) -> Result<Json, (i32, String)> {
+        let uri = params.get("textDocument").get("uri").as_str().unwrap_or("");
+        let path = path_of(self.fs, uri);
+
+        let result = match method {
+            "initialize" => Json::object(vec![
+                ("capabilities", Json::object(vec![
+                    // The whole document is sent on every change.
+                    ("textDocumentSync", Json::from(1)),
+                    ("definitionProvider", Json::Bool(true)),
+                    ("hoverProvider", Json::Bool(true)),
+                    ("documentSymbolProvider", Json::Bool(true)),
+                    ("completionProvider", Json::object(vec![
+                        ("triggerCharacters", Json::Array(vec![Json::string("<")])),
+                    ])),
+                ])),
+                ("serverInfo", Json::object(vec![("name", Json::string("mold"))])),
+            ]),
+            "initialized" | "shutdown" => Json::Null,
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => params.get("textDocument").get("text"),
+                    _ => params.get("contentChanges").as_array().and_then(|c| c.last()).map_or(&Json::Null, |c| c.get("text")),
+                };
+
+                if let (Some(path), Some(text)) = (path, text.as_str()) {
+                    self.documents.insert(path.clone(), text.to_owned());
+                    self.publish_diagnostics(uri, &path);
+                }
+
+                Json::Null
+            },
+            "textDocument/didClose" => {
+                if let Some(path) = path {
+                    self.documents.remove(&path);
+                }
+
+                self.outgoing.push(notification("textDocument/publishDiagnostics", Json::object(vec![
+                    ("uri", Json::string(uri)),
+                    ("diagnostics", Json::Array(Vec::new())),
+                ])));
+
+                Json::Null
+            },
+            "textDocument/definition" | "textDocument/hover" | "textDocument/completion" | "textDocument/documentSymbol" => {
+                let path = match path {
+                    Some(path) => path,
+                    None => return Ok(Json::Null),
+                };
+
+                let text = match self.text(&path) {
+                    Some(text) => text,
+                    None => return Ok(Json::Null),
+                };
+
+                let doc = self.parse(&path, &text).0;
+
+                let line = params.get("position").get("line").as_usize().unwrap_or(0);
+                let character = params.get("position").get("character").as_usize().unwrap_or(0);
+                let offset = offset(&text, line, character);
+
+                match method {
+                    "textDocument/definition" => self.definition(&doc, offset),
+                    "textDocument/hover" => self.hover(&doc, offset),
+                    "textDocument/completion" => self.completion(&doc),
+                    _ => symbols(&doc),
+                }
+            },
+            _ => return Err((METHOD_NOT_FOUND, format!("unknown method '{}'", method))),
+        };
+
+        Ok(result)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, path: &Path) {
+        let text = self.documents[path].clone();
+
+        let diagnostics = self.parse(path, &text).1.iter()
+            .map(|d| lsp_diagnostic(uri, &text, d))
+            .collect();
+
+        self.outgoing.push(notification("textDocument/publishDiagnostics", Json::object(vec![
+            ("uri", Json::string(uri)),
+            ("diagnostics", Json::Array(diagnostics)),
+        ])));
+    }
+
+    // Parses a document, also if it contains errors, and returns the errors as diagnostics.
+    fn parse<'t>(&self, path: &Path, text: &'t str) -> (Document<'t>, Vec<Diagnostic>) {
+        let kind = self.kind(path);
+
+        let mut doc = Document {
+            text,
+            kind,
+            body: Origin::START,
+            tree: None,
+        };
+
+        if kind == Kind::Page {
+            match front_matter::split(text) {
+                Ok((_, body)) => {
+                    let offset = text.len() - body.len();
+
+                    doc.body = Origin {
+                        line: diagnostic::line_of(text, offset),
+                        offset,
+                    };
+                },
+                Err(e) => return (doc, vec![Diagnostic::front_matter(path, Some(text), e)]),
+            }
+        }
+
+        let body = &text[doc.body.offset..];
+        let (tree, errors) = SyntaxTree::parse(body);
+
+        let diagnostics = errors.iter().map(|e| Diagnostic::syntax(path, body, doc.body, e)).collect();
+
+        doc.tree = Some(tree);
+
+        (doc, diagnostics)
+    }
+
+    fn definition(&self, doc: &Document, offset: usize) -> Json {
+        let tag = match doc.tag_at(offset) {
+            Some(tag) => tag,
+            None => return Json::Null,
+        };
+
+        let path = self.find(&self.templates, tag.name).or_else(|| self.find(&self.modules, tag.name));
+
+        match path {
+            Some(path) => Json::object(vec![
+                ("uri", Json::string(uri_of(&path))),
+                ("range", range("", 0..0)),
+            ]),
+            None => Json::Null,
+        }
+    }
+
+    // Shows which templates a template extends, or the file of a module.
+    fn hover(&self, doc: &Document, offset: usize) -> Json {
+        let tag = match doc.tag_at(offset) {
+            Some(tag) => tag,
+            None => return Json::Null,
+        };
+
+        let value = if self.find(&self.templates, tag.name).is_some() {
+            let names: Vec<String> = self.chain(tag.name).into_iter().map(|(name, _)| name).collect();
+            format!("template `{}`", names.join("` → `"))
+        } else if let Some(path) = self.find(&self.modules, tag.name) {
+            format!("module `{}`, {}", tag.name, path.display())
+        } else {
+            return Json::Null;
+        };
+
+        let name = doc.name_at(tag, offset);
+
+        Json::object(vec![
+            ("contents", Json::object(vec![
+                ("kind", Json::string("markdown")),
+                ("value", Json::string(value)),
+            ])),
+            ("range", range(doc.text, name.start + doc.body.offset..name.end + doc.body.offset)),
+        ])
+    }
+
+    // Offers the blocks of the template which the document extends.
+    fn completion(&self, doc: &Document) -> Json {
+        let tree = match (&doc.tree, doc.kind) {
+            (Some(tree), Kind::Template) | (Some(tree), Kind::Page) => tree.to_content_tree(),
+            _ => return Json::Array(Vec::new()),
+        };
+
+        let parent = match templates::parent_tag(&tree) {
+            Some(tag) if self.find(&self.templates, tag.name).is_some() => tag.name,
+            _ => return Json::Array(Vec::new()),
+        };
+
+        let items = self.blocks(parent).into_iter().map(|name| Json::object(vec![
+            ("label", Json::string(name)),
+            ("kind", Json::from(COMPLETION_PROPERTY)),
+            ("detail", Json::string(format!("block of '{}'", parent))),
+        ])).collect();
+
+        Json::Array(items)
+    }
+
+    // The template and the templates it extends, e.g. ['post', 'base'], with their texts.
+    fn chain(&self, name: &str) -> Vec<(String, String)> {
+        let mut chain: Vec<(String, String)> = Vec::new();
+        let mut name = name.to_owned();
+
+        while !chain.iter().any(|(n, _)| *n == name) {
+            let text = match self.find(&self.templates, &name).and_then(|path| self.text(&path)) {
+                Some(text) => text,
+                None => break,
+            };
+
+            let tree = SyntaxTree::parse(&text).0.to_content_tree();
+
+            // The next template is looked up on the next round, which ends the chain if there is none.
+            let parent = templates::parent_tag(&tree).map(|tag| tag.name.to_owned());
+
+            chain.push((name, text));
+
+            name = match parent {
+                Some(parent) => parent,
+                None => break,
+            };
+        }
+
+        chain
+    }
+
+    // The names of the blocks which content extending the template can fill in: those it declares,
+    // i.e. the tags inside the tag naming its parent, followed by those it inherits.
+    fn blocks(&self, name: &str) -> Vec<String> {
+        let chain = self.chain(name);
+        let mut blocks: Vec<String> = Vec::new();
+
+        for (_, text) in &chain {
+            let tree = SyntaxTree::parse(text).0.to_content_tree();
+
+            // Every template which the chain reached exists.
+            let declared = match templates::parent_tag(&tree) {
+                Some(tag) if chain.iter().any(|(n, _)| n == tag.name) => tag.content.tags(),
+                _ => tree.tags(),
+            };
+
+            for tag in declared {
+                if !blocks.iter().any(|b| b == tag.name) {
+                    blocks.push(tag.name.to_owned());
+                }
+            }
+        }
+
+        blocks
+    }
+
+    // Returns the file of the template or module with the name. The open documents are
+    // looked at first, so that the files of a directory are only listed, not read.
+    fn find(&self, dirs: &[PathBuf], name: &str) -> Option<PathBuf> {
+        let is_named = |p: &Path| p.file_stem().and_then(|s| s.to_str()) == Some(name);
+
+        for dir in dirs {
+            let open = self.documents.keys()
+                .filter(|p| p.parent() == Some(dir.as_path()) && is_named(p))
+                .min();
+
+            if let Some(path) = open {
+                return Some(path.clone());
+            }
+
+            let file = self.fs.read_dir(dir).unwrap_or_default().into_iter()
+                .filter(|f| !f.is_dir && is_named(&f.path))
+                .map(|f| f.path)
+                .min();
+
+            if file.is_some() {
+                return file;
+            }
+        }
+
+        None
+    }
+
+    // The text of an open document, or else of the file.
+    fn text(&self, path: &Path) -> Option<String> {
+        match self.documents.get(path) {
+            Some(text) => Some(text.clone()),
+            None => self.fs.read_to_string(path).ok(),
+        }
+    }
+
+    fn kind(&self, path: &Path) -> Kind {
+        if self.modules.iter().any(|d| path.starts_with(d)) {
+            Kind::Module
+        } else if self.content.iter().any(|d| path.starts_with(d)) {
+            Kind::Page
+        } else {
+            Kind::Template
+        }
+    }
+}
+
+impl<'t> Document<'t> {
+    // The innermost tag whose name in its opening or closing tag is at the offset of the text.
+    fn tag_at(&self, offset: usize) -> Option<&SyntaxTag<'t>> {
+        let offset = offset.checked_sub(self.body.offset)?;
+
+        fn find<'s, 't>(tree: &'s SyntaxTree<'t>, offset: usize) -> Option<&'s SyntaxTag<'t>> {
+            for node in &tree.children {
+                let tag = match node {
+                    SyntaxNode::Tag(tag) if tag.span.start <= offset && offset <= tag.span.end => tag,
+                    _ => continue,
+                };
+
+                if contains(&name_span(tag.span.start, tag.open), offset) {
+                    return Some(tag);
+                }
+
+                if !tag.close.is_empty() && contains(&name_span(tag.span.end - tag.close.len(), tag.close), offset) {
+                    return Some(tag);
+                }
+
+                let inner = tag.content.iter().chain(tag.branches.iter().map(|b| &b.content));
+                if let Some(tag) = inner.filter_map(|c| find(c, offset)).next() {
+                    return Some(tag);
+                }
+            }
+
+            None
+        }
+
+        find(self.tree.as_ref()?, offset)
+    }
+
+    // The name of the tag at the offset, in its opening or closing tag, in bytes of the body.
+    fn name_at(&self, tag: &SyntaxTag, offset: usize) -> Range<usize> {
+        let open = name_span(tag.span.start, tag.open);
+
+        if tag.close.is_empty() || contains(&open, offset - self.body.offset) {
+            open
+        } else {
+            name_span(tag.span.end - tag.close.len(), tag.close)
+        }
+    }
+}
+
+// The blocks of the document, with the blocks inside them as children.
+fn symbols(doc: &Document) -> Json {
+    fn symbols_of(doc: &Document, tree: &SyntaxTree) -> Vec<Json> {
+        let mut symbols = Vec::new();
+
+        for node in &tree.children {
+            let tag = match node {
+                SyntaxNode::Tag(tag) => tag,
+                _ => continue,
+            };
+
+            let content = match &tag.content {
+                Some(content) => content,
+                None => continue,
+            };
+
+            let mut children = symbols_of(doc, content);
+            for branch in &tag.branches {
+                children.append(&mut symbols_of(doc, &branch.content));
+            }
+
+            let name = name_span(tag.span.start, tag.open);
+            let o = doc.body.offset;
+
+            symbols.push(Json::object(vec![
+                ("name", Json::string(tag.name)),
+                ("detail", Json::string(tag.args)),
+                ("kind", Json::from(SYMBOL_NAMESPACE)),
+                ("range", range(doc.text, tag.span.start + o..tag.span.end + o)),
+                ("selectionRange", range(doc.text, name.start + o..name.end + o)),
+                ("children", Json::Array(children)),
+            ]));
+        }
+
+        symbols
+    }
+
+    match &doc.tree {
+        Some(tree) => Json::Array(symbols_of(doc, tree)),
+        None => Json::Null,
+    }
+}
+
+// Serves the client on the other end of 'input' and 'output' until it exits,
+// or the input ends. Messages are JSON-RPC, each preceded by a Content-Length header.
+pub fn serve<R: BufRead, W: Write>(server: &mut Server<'_>, mut input: R, mut output: W) -> io::Result<()> {
+    while let Some(message) = read_message(&mut input)? {
+        let message = match json::parse(&message) {
+            Some(message) => message,
+            None => {
+                write_message(&mut output, &response(&Json::Null, Err((PARSE_ERROR, "invalid JSON".to_owned()))))?;
+                continue;
+            },
+        };
+
+        let method = message.get("method").as_str().unwrap_or("");
+
+        if method == "exit" {
+            break;
+        }
+
+        let result = server.handle(method, message.get("params"));
+
+        for n in server.outgoing.drain(..) {
+            write_message(&mut output, &n)?;
+        }
+
+        // Notifications have no id, and get no response.
+        let id = message.get("id");
+        if *id != Json::Null {
+            write_message(&mut output, &response(id, result))?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_message<R: BufRead>(input: &mut R) -> io::Result<Option<String>> {
+    let mut length: Option<u64> = None;
+
+    loop {
+        let mut line = String::new();
+
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "message without Content-Length"))?;
+
+    // The body is read as it arrives, rather than allocated up front from a length which may be bogus.
+    let mut body = Vec::new();
+    input.by_ref().take(length).read_to_end(&mut body)?;
+
+    if body.len() as u64 != length {
+        return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "message shorter than its Content-Length"));
+    }
+
+    String::from_utf8(body).map(Some).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message<W: Write>(output: &mut W, message: &Json) -> io::Result<()> {
+    let body = message.to_string();
+
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+
+    output.flush()
+}
+
+fn response(id: &Json, result: Result<Json, (i32, String)>) -> Json {
+    let mut fields = vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("id", id.clone()),
+    ];
+
+    match result {
+        Ok(result) => fields.push(("result", result)),
+        Err((code, message)) => fields.push(("error", Json::object(vec![
+            ("code", Json::Number(code.into())),
+            ("message", Json::string(message)),
+        ]))),
+    }
+
+    Json::object(fields)
+}
+
+fn notification(method: &str, params: Json) -> Json {
+    Json::object(vec![
+        ("jsonrpc", Json::string("2.0")),
+        ("method", Json::string(method)),
+        ("params", params),
+    ])
+}
+
+fn lsp_diagnostic(uri: &str, text: &str, d: &Diagnostic) -> Json {
+    let location = |s: &diagnostic::Snippet| range(text, s.start..s.end);
+
+    let r = match (d.snippets.first(), d.line) {
+        (Some(s), _) => location(s),
+        (None, Some(line)) => range(text, offset(text, line - 1, 0)..offset(text, line, 0)),
+        (None, None) => range(text, 0..0),
+    };
+
+    let message = match &d.hint {
+        Some(hint) => format!("{}\nhint: {}", d.message, hint),
+        None => d.message.clone(),
+    };
+
+    let related = d.snippets.iter().skip(1).map(|s| Json::object(vec![
+        ("location", Json::object(vec![
+            ("uri", Json::string(uri)),
+            ("range", location(s)),
+        ])),
+        ("message", Json::string(s.label.clone().unwrap_or_default())),
+    ])).collect();
+
+    Json::object(vec![
+        ("range", r),
+        ("severity", Json::from(if d.is_error() { 1 } else { 2 })),
+        ("code", d.code.map_or(Json::Null, Json::string)),
+        ("source", Json::string("mold")),
+        ("message", Json::string(message)),
+        ("relatedInformation", Json::Array(related)),
+    ])
+}
+
+// The bytes of the name in an opening or closing tag which starts at 'start',
+// e.g. 'foo' of '<< foo bar<<'.
+fn name_span(start: usize, tag: &str) -> Range<usize> {
+    let rest = &tag[2..];
+    let start = start + 2 + rest.len() - rest.trim_start().len();
+    let name = diagnostic::tag_name(tag);
+
+    start..start + name.len()
+}
+
+// Whether the offset is in the span or right after it, where the cursor is after typing it.
+fn contains(span: &Range<usize>, offset: usize) -> bool {
+    span.start <= offset && offset <= span.end
+}
+
+// A position of the protocol: the line and the UTF-16 code units before it on the line, from 0.
+fn position(text: &str, offset: usize) -> Json {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+
+    Json::object(vec![
+        ("line", Json::from(before.matches('\n').count())),
+        ("character", Json::from(before[line_start..].encode_utf16().count())),
+    ])
+}
+
+fn range(text: &str, span: Range<usize>) -> Json {
+    Json::object(vec![
+        ("start", position(text, span.start)),
+        ("end", position(text, span.end)),
+    ])
+}
+
+// The byte offset of a position of the protocol, clamped to the line and text.
+fn offset(text: &str, line: usize, character: usize) -> usize {
+    let line_start: usize = text.split_inclusive('\n').take(line).map(|l| l.len()).sum();
+
+    let mut units = 0;
+
+    for (i, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + i;
+        }
+
+        units += c.len_utf16();
+    }
+
+    text.len()
+}
+
+fn absolute(fs: &dyn FileSystem, path: &Path) -> PathBuf {
+    fs.canonicalize(path).unwrap_or_else(|_| match env::current_dir() {
+        Ok(dir) => dir.join(path),
+        Err(_) => path.to_path_buf(),
+    })
+}
+
+fn path_of(fs: &dyn FileSystem, uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?;
+
+    // Decode percent-encoded bytes.
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        let hex = tail.get(..2).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+
+        match (b, hex) {
+            (b'%', Some(h)) => {
+                bytes.push(h);
+                rest = &tail[2..];
+            },
+            _ => {
+                bytes.push(b);
+                rest = tail;
+            },
+        }
+    }
+
+    let path = PathBuf::from(String::from_utf8(bytes).ok()?);
+
+    Some(absolute(fs, &path))
+}
+
+fn uri_of(path: &Path) -> String {
+    let segments: Vec<String> = path.to_string_lossy().split('/').map(urlencode).collect();
+
+    format!("file://{}", segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::file_system::DiskFs;
+    use crate::file_system::MemoryFs;
+
+    #[test]
+    fn positions_count_utf16() {
+        let text = "a\nå😀<<b>>\n";
+
+        assert_eq!(position(text, 8), json::parse(r#"{"line":1,"character":3}"#).unwrap());
+        assert_eq!(offset(text, 1, 3), 8);
+        assert_eq!(offset(text, 1, 100), 13);
+        assert_eq!(offset(text, 5, 0), text.len());
+    }
+
+    #[test]
+    fn uris_of_paths() {
+        let path = PathBuf::from("/tmp/a b/ä.html");
+
+        assert_eq!(uri_of(&path), "file:///tmp/a%20b/%C3%A4.html");
+        assert_eq!(path_of(&DiskFs, &uri_of(&path)), Some(path));
+        assert_eq!(path_of(&DiskFs, "http://example.com"), None);
+    }
+
+    #[test]
+    fn names_in_tags() {
+        assert_eq!(name_span(10, "<< foo bar<<"), 13..16);
+        assert_eq!(name_span(0, ">>foo>>"), 2..5);
+    }
+
+    #[test]
+    fn tag_at_offset() {
+        let text = "<<base<<<<title<<x>>title>>>>base>>";
+
+        let doc = Document {
+            text,
+            kind: Kind::Page,
+            body: Origin::START,
+            tree: Some(SyntaxTree::parse(text).0),
+        };
+
+        assert_eq!(doc.tag_at(3).map(|t| t.name), Some("base"));
+        assert_eq!(doc.tag_at(12).map(|t| t.name), Some("title"));
+        assert_eq!(doc.tag_at(22).map(|t| t.name), Some("title"));
+        assert_eq!(doc.tag_at(31).map(|t| t.name), Some("base"));
+        assert_eq!(doc.tag_at(18).map(|t| t.name), None);
+    }
+
+    #[test]
+    fn blocks_of_parent_template() {
+        let fs: MemoryFs = vec![
+            ("templates/base.html", "<title><<title>></title><<content>>"),
+            ("templates/post.html", "<<base<<<<content<<<article><<body>></article>>>content>>>>base>>"),
+            ("templates/note.html", "<<post<<<<title<<Note>>title>>>>post>>"),
+        ].into_iter().collect();
+
+        let server = Server::new(&fs, &[PathBuf::from("templates")], &[], &[]);
+
+        assert_eq!(server.blocks("base"), vec!["title", "content"]);
+        assert_eq!(server.blocks("post"), vec!["content", "title"]);
+        assert_eq!(server.blocks("note"), vec!["title", "content"]);
+    }
+
+    #[test]
+    fn blocks_of_open_documents() {
+        let fs: MemoryFs = vec![
+            ("templates/base.html", "<title><<title>></title><<content>>"),
+            ("templates/post.html", "<<base<<<<content<<<<body>>>>content>>>>base>>"),
+        ].into_iter().collect();
+
+        let mut server = Server::new(&fs, &[PathBuf::from("templates")], &[], &[]);
+
+        // Unsaved changes, and a template which isn't on disk yet.
+        server.documents.insert(PathBuf::from("templates/post.html"), "<<base<<<<title<<<<head>>>>title>>>>base>>".to_owned());
+        server.documents.insert(PathBuf::from("templates/note.html"), "<<post<<<<aside>>>>post>>".to_owned());
+
+        assert_eq!(server.blocks("post"), vec!["title", "content"]);
+        assert_eq!(server.blocks("note"), vec!["aside", "title", "content"]);
+        assert_eq!(server.find(&server.templates, "note"), Some(PathBuf::from("templates/note.html")));
+        assert_eq!(server.find(&server.templates, "missing"), None);
+    }
+
+    #[test]
+    fn reads_message_of_bogus_length() {
+        let mut input = io::Cursor::new("Content-Length: 18446744073709551615\r\n\r\n{}");
+
+        let e = read_message(&mut input).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut input = io::Cursor::new("Content-Length: 2\r\n\r\n{}");
+        assert_eq!(read_message(&mut input).unwrap(), Some("{}".to_owned()));
+    }
+}
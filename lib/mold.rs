@@ -1,23 +1,98 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
 use std::path::PathBuf;
 
-use tempfile::{TempDir, Builder};
-
+use crate::compile::Program;
+use crate::content_tree;
+use crate::content_tree::ContentTree;
+use crate::content_tree::OwnedContentTree;
+use crate::content_tree::Tag;
+use crate::context::Context;
+use crate::context::Value;
+use crate::date;
+use crate::diagnostic;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Origin;
+use crate::error;
+use crate::error::Error;
+use crate::feeds::Entry;
+use crate::feeds::Feed;
+use crate::feeds::FeedContent;
+use crate::file_system::DiskFs;
+use crate::file_system::FileSystem;
+use crate::file_system::MemoryFs;
+use crate::filters::Filters;
+use crate::format;
+use crate::front_matter;
+use crate::lsp;
+use crate::output;
+use crate::pages;
+use crate::pages::CompiledPage;
+use crate::pages::Page;
+use crate::plugin::Plugin;
+use crate::render::Renderer;
+use crate::sitemap;
+use crate::source;
 use crate::templates;
 use crate::statics;
+use crate::syntax::SyntaxTree;
+use crate::tags::Tags;
+use crate::taxonomies::Taxonomy;
 
 pub struct Mold {
+    // Where the project is read from and the site is built into
+    fs: Box<dyn FileSystem>,
     paths: Paths,
+    filters: Filters,
+    tags: Tags,
+    plugins: Vec<Box<dyn Plugin>>,
+    taxonomies: Vec<String>,
+    // The collections with feeds
+    feeds: Vec<(String, FeedContent)>,
+    title: String,
+    // The address the site is published at, used for absolute urls
+    base_url: Option<String>,
+    // Variables from the configuration file, available as 'site'
+    site: BTreeMap<String, Value>,
+    drafts: bool,
+    future: bool,
 }
 
 impl Mold {
     pub fn new<D: Into<PathBuf>>(output: D) -> Mold {
-        let mut m = Mold {
-            ..Default::default()
-        };
+        Mold {
+            fs: Box::new(DiskFs),
+            paths: Paths {
+                output: output.into(),
+                ..Default::default()
+            },
+            filters: Filters::default(),
+            tags: Tags::new(),
+            plugins: Vec::new(),
+            taxonomies: Vec::new(),
+            feeds: Vec::new(),
+            title: String::new(),
+            base_url: None,
+            site: BTreeMap::new(),
+            drafts: false,
+            future: false,
+        }
+    }
 
-        m.paths.output = output.into();
+    // Reads the project from, and builds the site into, another file system than the disk,
+    // e.g. a 'MemoryFs' to build a site without touching the disk. The paths are then
+    // paths of that file system.
+    pub fn set_file_system<F: FileSystem + 'static>(&mut self, fs: F) {
+        self.fs = Box::new(fs);
+    }
 
-        m
+    // Sets the directory the site is built in before it replaces the output directory.
+    // It must be on the same file system as the output directory. By default it is '.mold' next to the output directory.
+    pub fn set_build_dir<D: Into<PathBuf>>(&mut self, dir: D) {
+        self.paths.build = Some(dir.into());
     }
 
     pub fn add_templates_dir<D: Into<PathBuf>>(&mut self, dir: D) {
@@ -36,24 +111,914 @@ impl Mold {
         self.paths.statics.push(dir.into());
     }
 
-    pub fn build(&self) {
-        templates::flatten_templates(&self.paths.templates, &self.paths.build);
+    // Adds a filter for use in variable references, e.g. '<<page.title | shout>>'.
+    // The filter receives the value and the arguments following its name.
+    // A filter with the same name as a built-in filter replaces it.
+    pub fn add_filter<F>(&mut self, name: &str, filter: F) where
+        F: Fn(&Value, &[Value]) -> Result<Value, String> + 'static {
+        self.filters.insert(name, filter);
+    }
+
+    // Adds a tag implemented in Rust, e.g. '<<now>>'. The handler receives the tag as it is
+    // written, with its arguments and unrendered content, and the variables where it is used.
+    // What it returns replaces the tag, while an error fails the build.
+    // A custom tag replaces a module with the same name, but not 'for' and 'if'.
+    pub fn register_tag<F>(&mut self, name: &str, handler: F) where
+        F: Fn(&Tag, &Context) -> Result<String, String> + 'static {
+        self.tags.insert(name, handler);
+    }
+
+    // Adds a plugin, whose hooks are called during builds, see 'Plugin'.
+    // Plugins are called in the order they were added.
+    pub fn add_plugin<P: Plugin + 'static>(&mut self, plugin: P) {
+        self.plugins.push(Box::new(plugin));
+    }
+
+    // Adds a front matter list, e.g. 'tags', whose values get their own pages.
+    // Each term is listed at '/tags/<term>/' using the template 'tags-term' or 'term',
+    // and all terms are listed at '/tags/' using the template 'tags' or 'taxonomy'.
+    pub fn add_taxonomy<S: Into<String>>(&mut self, name: S) {
+        self.taxonomies.push(name.into());
+    }
+
+    // Generates RSS and Atom feeds of a collection at '/<collection>/rss.xml' and
+    // '/<collection>/atom.xml'. If there is a template named 'rss' or 'atom',
+    // it is used instead, with the entries in the 'feed' variable.
+    // Feeds require a base url.
+    pub fn add_feed<S: Into<String>>(&mut self, collection: S, content: FeedContent) {
+        self.feeds.push((collection.into(), content));
+    }
+
+    pub fn set_title<S: Into<String>>(&mut self, title: S) {
+        self.title = title.into();
+    }
+
+    pub fn set_base_url<S: Into<String>>(&mut self, url: S) {
+        self.base_url = Some(url.into());
+    }
+
+    // Whether to build pages with 'draft: true' in their front matter.
+    pub fn include_drafts(&mut self, drafts: bool) {
+        self.drafts = drafts;
+    }
+
+    // Whether to build pages with a 'date' in the future.
+    pub fn include_future(&mut self, future: bool) {
+        self.future = future;
+    }
+
+    // Reads settings from a file of 'key: value' lines, using the syntax of front matter.
+    //
+    // title: My Site
+    // base_url: https://example.com
+    // taxonomies: [tags]
+    // feeds: [posts]
+    // feed_content: summary
+    //
+    // All settings, including unknown ones, are available in templates as 'site'.
+    pub fn load_config<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+
+        let config = front_matter::parse(&self.fs.read_to_string(path)?, 1)
+            .map_err(|e| Error::FrontMatter(path.to_path_buf(), e))?;
+
+        let content = match config.get("feed_content").map(|v| v.to_string()).as_deref() {
+            None | Some("full") => FeedContent::Full,
+            Some("summary") => FeedContent::Summary,
+            Some(c) => return Err(Error::Config(format!("unknown feed content '{}'", c))),
+        };
+
+        for (key, value) in &config {
+            match (key.as_ref(), value) {
+                ("title", v) => self.set_title(v.to_string()),
+                ("base_url", v) => self.set_base_url(v.to_string()),
+                ("taxonomies", Value::List(l)) => l.iter().for_each(|t| self.add_taxonomy(t.to_string())),
+                ("feeds", Value::List(l)) => l.iter().for_each(|c| self.add_feed(c.to_string(), content)),
+                ("taxonomies", _) | ("feeds", _) => return Err(Error::Config(format!("'{}' must be a list", key))),
+                _ => (),
+            }
+        }
+
+        self.site.extend(config);
+
+        Ok(())
+    }
+
+    // Builds the site in the build directory, see 'set_build_dir', and only swaps it with
+    // the output directory once the whole site has been built. On failure the previous output is kept.
+    // Returns the warnings, such as variables that don't exist, sorted by file and line like 'check'.
+    // When the build fails with 'Error::Diagnostics', the warnings are among them.
+    pub fn build(&self) -> Result<Vec<Diagnostic>, Error> {
+        let fs = self.fs.as_ref();
+
+        let stage = output::stage(fs, &self.paths.build())?;
+
+        let mut warnings = Vec::new();
+
+        let built = self.build_into(fs, &stage, &mut warnings)
+            .and_then(|()| self.finish(fs, &stage));
+
+        if let Err(e) = built {
+            fs.remove_dir_all(&stage)?;
+
+            return Err(with_warnings(e, warnings));
+        }
+
+        output::replace(fs, &stage, &self.paths.output)?;
+        output::write_manifest(fs, &self.paths.output, &self.paths.build())?;
+
+        warnings.sort();
+        warnings.dedup();
+
+        Ok(warnings)
+    }
+
+    // Builds the site into the output directory on 'dest' instead of the file system of the
+    // project, e.g. into a 'MemoryFs' to serve it from. Unlike 'build', the site is written in
+    // place, and 'dest' may be left with part of it on failure. Returns the warnings like 'build'.
+    pub fn build_to(&self, dest: &dyn FileSystem) -> Result<Vec<Diagnostic>, Error> {
+        let mut warnings = Vec::new();
+
+        let built = self.build_into(dest, &self.paths.output, &mut warnings)
+            .and_then(|()| self.finish(dest, &self.paths.output));
+
+        if let Err(e) = built {
+            return Err(with_warnings(e, warnings));
+        }
+
+        warnings.sort();
+        warnings.dedup();
+
+        Ok(warnings)
+    }
+
+    // Calls the hooks of the plugins on the files of a build.
+    // Unlike the rest of a build, this isn't part of 'check'.
+    fn finish(&self, fs: &dyn FileSystem, stage: &Path) -> Result<(), Error> {
+        for plugin in &self.plugins {
+            plugin.statics_copied(fs, stage)?;
+        }
+
+        for plugin in &self.plugins {
+            plugin.build_finished(fs, stage)?;
+        }
+
+        Ok(())
+    }
+
+    // Looks for problems in the project without touching the output directory,
+    // by building the site in memory. Besides the errors which fail
+    // the build, this finds variables that don't exist and pages extending
+    // templates that don't exist. The diagnostics are sorted by file and line.
+    // Plugins change the templates and pages like in a build, but their hooks
+    // on the files of a build aren't called.
+    pub fn check(&self) -> Result<Vec<Diagnostic>, Error> {
+        let mut diagnostics = Vec::new();
+
+        match self.build_into(&MemoryFs::new(), &self.paths.output, &mut diagnostics) {
+            Ok(()) => (),
+            Err(Error::Io(e)) => return Err(Error::Io(e)),
+            Err(e) => diagnostics.append(&mut e.into_diagnostics()),
+        }
+
+        diagnostics.sort();
+        diagnostics.dedup();
+
+        Ok(diagnostics)
+    }
+
+    // Removes the output directory, and anything left behind by interrupted builds.
+    // Nothing outside of 'root', the directory of the project, is removed.
+    pub fn clean<P: AsRef<Path>>(&self, root: P) -> Result<(), Error> {
+        output::clean(self.fs.as_ref(), &self.paths.output, &self.paths.build(), root.as_ref())
+    }
+
+    // Formats the templates, modules and pages of the project, see 'format::format',
+    // and returns the files which weren't formatted, sorted. With 'overwrite' they are
+    // rewritten. Nothing is written when any of the files has errors.
+    pub fn format(&self, overwrite: bool) -> Result<Vec<PathBuf>, Error> {
+        let mut diagnostics = Vec::new();
+
+        let mut templates = Vec::new();
+        for dir in &self.paths.templates {
+            templates.append(&mut source::read_dir(self.fs.as_ref(), dir)?);
+        }
+
+        let mut modules = Vec::new();
+        for dir in &self.paths.modules {
+            modules.append(&mut source::read_dir(self.fs.as_ref(), dir)?);
+        }
+
+        let mut pages = Vec::new();
+        for dir in &self.paths.content {
+            pages.append(&mut pages::read_pages(self.fs.as_ref(), dir, &mut diagnostics)?);
+        }
+
+        let names: Vec<&str> = templates.iter().map(|f| f.name.as_ref()).collect();
+
+        // The files which change, with their formatted content
+        let mut changed = Vec::new();
+
+        for f in &templates {
+            if check_syntax(&f.path, &f.content, Origin::START, &mut diagnostics).is_some() {
+                let (tree, _) = SyntaxTree::parse(&f.content);
+                changed.push((f.path.clone(), f.content.clone(), format::format(&tree, &names)));
+            }
+        }
+
+        // Modules don't extend templates.
+        for f in &modules {
+            if check_syntax(&f.path, &f.content, Origin::START, &mut diagnostics).is_some() {
+                let (tree, _) = SyntaxTree::parse(&f.content);
+                changed.push((f.path.clone(), f.content.clone(), format::format(&tree, &[])));
+            }
+        }
+
+        for page in &pages {
+            if check_syntax(&page.file, &page.body, page.body_start, &mut diagnostics).is_some() {
+                let (tree, _) = SyntaxTree::parse(&page.body);
+
+                // Keep the front matter as it is.
+                let source = self.fs.read_to_string(&page.file)?;
+                let formatted = format!("{}{}", &source[..page.body_start.offset], format::format(&tree, &names));
+
+                changed.push((page.file.clone(), source, formatted));
+            }
+        }
+
+        fail_on_errors(diagnostics, &mut Vec::new())?;
+
+        changed.retain(|(_, source, formatted)| source != formatted);
+        changed.sort();
+
+        if overwrite {
+            for (path, _, formatted) in &changed {
+                self.fs.write(path, formatted.as_bytes())?;
+            }
+        }
+
+        Ok(changed.into_iter().map(|(path, _, _)| path).collect())
+    }
+
+    // Prints the tree which a file of the project is parsed into, as an outline or as JSON,
+    // see 'SyntaxTree::outline' and 'SyntaxTree::to_json'. The spans are in bytes of the file,
+    // so for pages the front matter comes before the first node. The tree is printed even if
+    // the file has syntax errors, which are returned along with it.
+    pub fn inspect<P: AsRef<Path>>(&self, path: P, json: bool) -> Result<(String, Vec<Diagnostic>), Error> {
+        let path = path.as_ref();
+
+        let source = self.fs.read_to_string(path)?;
+
+        let canonical = |p: &Path| self.fs.canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        let is_page = self.paths.content.iter().any(|d| canonical(path).starts_with(canonical(d)));
+
+        let mut body = Origin::START;
+
+        if is_page {
+            let (_, b) = front_matter::split(&source).map_err(|e| Error::FrontMatter(path.to_path_buf(), e))?;
+
+            let offset = source.len() - b.len();
+            body = Origin {
+                line: diagnostic::line_of(&source, offset),
+                offset,
+            };
+        }
+
+        let input = &source[body.offset..];
+        let (tree, errors) = SyntaxTree::parse(input);
+
+        let diagnostics = errors.iter().map(|e| Diagnostic::syntax(path, input, body, e)).collect();
+
+        let out = if json {
+            format!("{}\n", tree.to_json(body.offset))
+        } else {
+            tree.outline(body.offset)
+        };
+
+        Ok((out, diagnostics))
+    }
+
+    // Renders a string like a page of the site, without building the site: it may have
+    // front matter and extend a template, and can use the modules and all variables of the site,
+    // e.g. the collections. The files of the project are read for every call, see 'prepared'
+    // to render several pages.
+    pub fn render_str(&self, source: &str) -> Result<String, Error> {
+        self.prepared(|p| p.render_str(source))?
+    }
+
+    // Renders a file like a page of the site, see 'render_str'. The file may be
+    // outside of the content directories, in which case 'page.url' is its name.
+    pub fn render_file<P: AsRef<Path>>(&self, path: P) -> Result<String, Error> {
+        self.prepared(|p| p.render_file(path))?
+    }
+
+    // Reads the project once and calls 'f', which can render any number of pages with it,
+    // see 'Prepared'. Changes to the files of the project aren't seen during 'f'.
+    pub fn prepared<T, F: FnOnce(&Prepared) -> T>(&self, f: F) -> Result<T, Error> {
+        let mut sources = Sources::default();
+        let site = self.prepare(&mut sources, &mut Vec::new())?;
+
+        Ok(f(&Prepared { mold: self, site }))
+    }
+
+    // Runs a language server for the files of the project until the client exits.
+    // Editors talk to it over 'input' and 'output', usually stdin and stdout.
+    pub fn lsp<R: BufRead, W: io::Write>(&self, input: R, output: W) -> Result<(), Error> {
+        let mut server = lsp::Server::new(&*self.fs, &self.paths.templates, &self.paths.modules, &self.paths.content);
+
+        lsp::serve(&mut server, input, output)?;
+
+        Ok(())
+    }
+
+    // Reads and parses all files of the project, and adds their problems to 'diagnostics'.
+    // Every file is read, even if others contain errors. Returns the pages and modules which could be read.
+    fn read_sources(&self, diagnostics: &mut Vec<Diagnostic>) -> io::Result<(Vec<Page>, HashMap<String, OwnedContentTree>)> {
+        let mut templates = Vec::new();
+        for dir in &self.paths.templates {
+            templates.append(&mut source::read_dir(self.fs.as_ref(), dir)?);
+        }
+
+        let mut modules = Vec::new();
+        for dir in &self.paths.modules {
+            modules.append(&mut source::read_dir(self.fs.as_ref(), dir)?);
+        }
+
+        let mut trees = Vec::new();
+        for f in &templates {
+            if let Some(tree) = check_syntax(&f.path, &f.content, Origin::START, diagnostics) {
+                trees.push((f.name.as_ref(), tree));
+            }
+        }
+
+        let mut parsed = HashMap::new();
+        for f in &modules {
+            if let Some(tree) = check_syntax(&f.path, &f.content, Origin::START, diagnostics) {
+                parsed.insert(f.name.clone(), tree.into_owned());
+            }
+        }
+
+        for name in templates::extending_themselves(trees.iter().map(|(n, t)| (*n, t)).collect()) {
+            let f = templates.iter().find(|f| f.name == name).unwrap();
+
+            let message = format!("template '{}' extends itself", name);
+            diagnostics.push(Diagnostic::error(&f.path, Some(1), message).with_code(error::TEMPLATE_RECURSION));
+        }
+
+        let mut pages = Vec::new();
+        for dir in &self.paths.content {
+            pages.append(&mut pages::read_pages(self.fs.as_ref(), dir, diagnostics)?);
+        }
+
+        for page in &pages {
+            let tree = match check_syntax(&page.file, &page.body, page.body_start, diagnostics) {
+                Some(tree) => tree,
+                None => continue,
+            };
+
+            // A page consisting of a single block is meant to extend a template.
+            let parent = templates::parent_tag(&tree)
+                .filter(|tag| !tag.content.children.is_empty())
+                .filter(|tag| tag.name != "for" && tag.name != "if")
+                .filter(|tag| !templates.iter().chain(&modules).any(|f| f.name == tag.name))
+                .filter(|tag| !self.tags.contains(tag.name));
+
+            if let Some(tag) = parent {
+                let message = format!("extends '{}', which is not a template", tag.name);
+                let d = Diagnostic::warning(&page.file, Some(page.body_start.line), message);
+                diagnostics.push(d.with_code(error::UNKNOWN_TEMPLATE));
+            }
+        }
+
+        Ok((pages, parsed))
+    }
+
+    // Flattens the templates, and lets the plugins change them.
+    fn flatten_templates(&self) -> Result<HashMap<String, OwnedContentTree>, Error> {
+        let mut templates = templates::flatten_templates(self.fs.as_ref(), &self.paths.templates)?;
+
+        for plugin in &self.plugins {
+            plugin.templates_flattened(&mut templates)?;
+        }
+
+        Ok(templates)
+    }
+
+    // Reads the project into 'sources', failing on errors in any of its files, and prepares
+    // the site for rendering, see 'Site'. Warnings are added to 'warnings'.
+    fn prepare<'s>(&'s self, sources: &'s mut Sources, warnings: &mut Vec<Diagnostic>) -> Result<Site<'s>, Error> {
+        let mut diagnostics = Vec::new();
+
+        let (mut pages, modules) = self.read_sources(&mut diagnostics)?;
+
+        fail_on_errors(diagnostics, warnings)?;
+
+        sources.modules = modules;
+        sources.templates = self.flatten_templates()?;
+
+        let sources: &'s Sources = sources;
+
+        let modules = sources.modules.iter().map(|(name, m)| (name.as_ref(), m.as_tree())).collect();
+        let renderer = Renderer::new(&modules, &self.filters, &self.tags);
+
+        // Templates are compiled once, and then rendered for every page.
+        let templates = sources.templates.iter()
+            .map(|(name, t)| (name.as_ref(), renderer.compile(&t.as_tree())))
+            .collect();
+
+        let mut collections: Vec<String> = pages.iter().filter_map(|p| p.collection()).collect();
+        collections.sort();
+        collections.dedup();
+
+        let taxonomies = self.publish(&mut pages);
+
+        Ok(Site {
+            pages,
+            collections,
+            taxonomies,
+            renderer,
+            templates,
+        })
+    }
+
+    // Leaves out the pages which aren't published, and collects the taxonomies of the rest.
+    // Unpublished pages are left out of everything, including collections, feeds and the sitemap.
+    // When they are included, they are marked with 'page.unpublished'.
+    fn publish(&self, pages: &mut Vec<Page>) -> Vec<Taxonomy> {
+        let now = date::now();
+        pages.retain(|p| (self.drafts || !p.is_draft()) && (self.future || !p.is_scheduled(now)));
+        for page in pages.iter_mut() {
+            if page.is_draft() || page.is_scheduled(now) {
+                page.variables.insert("unpublished".to_owned(), Value::Bool(true));
+            }
+        }
+
+        let taxonomies: Vec<Taxonomy> = self.taxonomies.iter()
+            .map(|t| Taxonomy::collect(t, pages))
+            .collect();
+
+        // Let pages link to their terms, e.g. using 'page.terms.tags'.
+        if !taxonomies.is_empty() {
+            for page in pages.iter_mut() {
+                let terms = taxonomies.iter()
+                    .map(|t| (t.name.clone(), t.page_value(page)))
+                    .collect();
+
+                page.variables.insert("terms".to_owned(), Value::Map(terms));
+            }
+        }
+
+        taxonomies
+    }
+
+    // The variables of the whole site: the collections, 'site' and 'taxonomies'.
+    fn site_context(&self, collections: &[String], pages: &[Page], taxonomies: &[Taxonomy]) -> Context {
+        // Every subdirectory of the content directories is a collection of pages.
+        let mut collections: BTreeMap<String, Vec<Value>> = collections.iter()
+            .map(|c| (c.clone(), Vec::new()))
+            .collect();
+        for page in pages {
+            if let Some(c) = page.collection() {
+                collections.entry(c).or_default().push(page.to_value());
+            }
+        }
+
+        let mut ctx = Context::new();
+        for (name, items) in collections {
+            ctx.insert(name, Value::List(items));
+        }
+
+        let mut site = self.site.clone();
+        site.insert("title".to_owned(), Value::String(self.title.clone()));
+        site.insert("base_url".to_owned(), Value::String(self.base_url.clone().unwrap_or_default()));
+        site.insert("drafts".to_owned(), Value::Bool(self.drafts));
+        ctx.insert("site", Value::Map(site));
+
+        if !taxonomies.is_empty() {
+            let t = taxonomies.iter()
+                .map(|t| (t.name.clone(), t.to_value(pages)))
+                .collect();
+
+            ctx.insert("taxonomies", Value::Map(t));
+        }
+
+        ctx
+    }
+
+    // Builds the site into the given directory of 'dest', while the project is read
+    // from the file system of Mold. Warnings, such as variables which
+    // couldn't be resolved, are added to 'warnings'. Errors in the files of the
+    // project are collected from all files before failing with 'Error::Diagnostics'.
+    fn build_into(
+        &self,
+        dest: &dyn FileSystem,
+        output: &Path,
+        warnings: &mut Vec<Diagnostic>
+    ) -> Result<(), Error> {
+        if !self.feeds.is_empty() && self.base_url.is_none() {
+            return Err(Error::Config("feeds require a base url".to_owned()));
+        }
+
+        let mut sources = Sources::default();
+        let Site { mut pages, collections, taxonomies, renderer, templates } = self.prepare(&mut sources, warnings)?;
+
+        for plugin in &self.plugins {
+            for page in &mut pages {
+                plugin.before_page(page)?;
+            }
+        }
+
+        for (collection, _) in &self.feeds {
+            // A feed of a collection with only unpublished pages is empty.
+            if !collections.contains(collection) {
+                return Err(Error::Config(format!("feed of unknown collection '{}'", collection)));
+            }
+        }
+
+        let mut ctx = self.site_context(&collections, &pages, &taxonomies);
+
+        // Pages are compiled once, after the plugins have changed them, for the page and its feed.
+        let compiled = pages.iter()
+            .map(|page| page.compile(&templates, &renderer))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // All rendered pages, except those with 'sitemap: false' in their front matter.
+        let mut sitemap = Vec::new();
+
+        // Render errors of all pages are reported together.
+        let mut errors = Vec::new();
+
+        // The output of each page, reused for the next one
+        let mut out = String::new();
+
+        for c in &compiled {
+            let page = c.page;
+
+            if let Err(e) = c.render(&templates, &renderer, &mut ctx, &mut out) {
+                errors.append(&mut e.into_diagnostics());
+                renderer.take_unresolved();
+                continue;
+            }
+
+            warn_unresolved(&renderer, &page.file, warnings);
+
+            for plugin in &self.plugins {
+                plugin.after_page(page, &mut out)?;
+            }
+
+            write_output(dest, output, &page.path, &out)?;
+
+            if page.front_matter.get("sitemap") != Some(&Value::Bool(false)) {
+                sitemap.push(sitemap::Entry {
+                    url: page.url(),
+                    lastmod: page.lastmod(),
+                });
+            }
+        }
+
+        // Like those of pages, errors of the taxonomy and term pages are collected.
+        for taxonomy in &taxonomies {
+            let name = &taxonomy.name;
+
+            match find_template(&templates, &[name, "taxonomy"]) {
+                Ok((t, template)) => {
+                    ctx.push_scope();
+                    ctx.insert("taxonomy", taxonomy.to_value(&pages));
+                    ctx.insert("page", generated_page(name, &taxonomy.url()));
+
+                    out.clear();
+                    let result = renderer.render_into(&mut out, template, None, &mut ctx);
+
+                    ctx.pop_scope();
+
+                    match result {
+                        Ok(()) => {
+                            write_output(dest, output, &taxonomy.path(), &out)?;
+                            warn_unresolved(&renderer, Path::new(t), warnings);
+                        },
+                        Err(e) => {
+                            errors.append(&mut Error::Render(PathBuf::from(t), e).into_diagnostics());
+                            renderer.take_unresolved();
+                        },
+                    }
+                },
+                Err(e) => errors.append(&mut e.into_diagnostics()),
+            }
+
+            sitemap.push(sitemap::Entry {
+                url: taxonomy.url(),
+                lastmod: None,
+            });
+
+            let (t, template) = match find_template(&templates, &[&format!("{}-term", name), "term"]) {
+                Ok(found) => found,
+                Err(e) => {
+                    errors.append(&mut e.into_diagnostics());
+                    continue;
+                },
+            };
+
+            for term in &taxonomy.terms {
+                ctx.push_scope();
+                ctx.insert("taxonomy", taxonomy.to_value(&pages));
+                ctx.insert("term", taxonomy.term_value(term, &pages));
+                ctx.insert("page", generated_page(&term.name, &taxonomy.term_url(term)));
+
+                out.clear();
+                let result = renderer.render_into(&mut out, template, None, &mut ctx);
+
+                ctx.pop_scope();
+
+                match result {
+                    Ok(()) => {
+                        write_output(dest, output, &taxonomy.term_path(term), &out)?;
+                        warn_unresolved(&renderer, Path::new(t), warnings);
+                    },
+                    Err(e) => {
+                        errors.append(&mut Error::Render(PathBuf::from(t), e).into_diagnostics());
+                        renderer.take_unresolved();
+                    },
+                }
+
+                sitemap.push(sitemap::Entry {
+                    url: taxonomy.term_url(term),
+                    lastmod: None,
+                });
+            }
+        }
+
+        fail_on_errors(errors, warnings)?;
+
+        self.build_feeds(dest, output, &compiled, &templates, &renderer, &mut ctx, warnings)?;
+
+        // Sitemaps require absolute urls.
+        if let Some(base_url) = &self.base_url {
+            write_output(dest, output, Path::new("sitemap.xml"), &sitemap::sitemap(base_url, &sitemap))?;
+
+            // A robots.txt of the project takes precedence.
+            if !self.paths.statics.iter().any(|d| self.fs.exists(&d.join("robots.txt"))) {
+                let url = format!("{}/sitemap.xml", base_url.trim_end_matches('/'));
+                write_output(dest, output, Path::new("robots.txt"), &sitemap::robots(&url))?;
+            }
+        }
 
         // Copy static files to output directory
         for dir in &self.paths.statics {
-            statics::copy_static_files(dir, &self.paths.output);
+            statics::copy_static_files(self.fs.as_ref(), dir, dest, output)?;
+        }
+
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn build_feeds(
+        &self,
+        dest: &dyn FileSystem,
+        output: &Path,
+        pages: &[CompiledPage],
+        templates: &HashMap<&str, Program>,
+        renderer: &Renderer,
+        ctx: &mut Context,
+        warnings: &mut Vec<Diagnostic>
+    ) -> Result<(), Error> {
+        let base_url = match &self.base_url {
+            Some(url) => url.trim_end_matches('/'),
+            None => return Ok(()),
+        };
+
+        let now = date::now();
+
+        for (collection, content) in &self.feeds {
+            let mut feed = Feed {
+                title: if self.title.is_empty() { collection.clone() } else { self.title.clone() },
+                url: format!("{}/{}/", base_url, collection),
+                entries: Vec::new(),
+            };
+
+            for c in pages.iter().filter(|c| c.page.collection().as_ref() == Some(collection)) {
+                let page = c.page;
+
+                let html = match content {
+                    FeedContent::Full => {
+                        let html = c.render_content(renderer, ctx)?;
+                        warn_unresolved(renderer, &page.file, warnings);
+                        html
+                    },
+                    FeedContent::Summary => page.front_matter.get("summary")
+                        .map(|s| s.to_string())
+                        .unwrap_or_default(),
+                };
+
+                feed.entries.push(Entry {
+                    title: page.front_matter.get("title").map_or_else(|| page.url(), |t| t.to_string()),
+                    url: format!("{}{}", base_url, page.url()),
+                    date: page.date(),
+                    content: html,
+                });
+            }
+
+            feed.sort();
+
+            for format in &["rss", "atom"] {
+                let path = PathBuf::from(collection).join(format!("{}.xml", format));
+                let feed_url = format!("{}/{}/{}.xml", base_url, collection, format);
+
+                let out = match templates.get(format) {
+                    Some(template) => {
+                        ctx.push_scope();
+                        ctx.insert("feed", feed.to_value(&feed_url, now));
+
+                        let mut out = String::new();
+                        let result = renderer.render_into(&mut out, template, None, ctx);
+
+                        ctx.pop_scope();
+
+                        result.map_err(|e| Error::Render(PathBuf::from(format), e))?;
+                        warn_unresolved(renderer, Path::new(format), warnings);
+                        out
+                    },
+                    None if *format == "rss" => feed.rss(&feed_url, now),
+                    None => feed.atom(&feed_url, now, *content),
+                };
+
+                write_output(dest, output, &path, &out)?;
+            }
+        }
+
+        Ok(())
+    }
+
+}
+
+// Writes a file to the output directory, creating its parent directories.
+fn write_output(fs: &dyn FileSystem, output: &Path, path: &Path, content: &str) -> io::Result<()> {
+    let path = output.join(path);
+
+    fs.create_dir_all(path.parent().unwrap())?;
+
+    fs.write(&path, content.as_bytes())
+}
+
+// Parses a file, or adds diagnostics pointing to its syntax errors.
+// The input starts at 'origin' in the file, e.g. after front matter.
+fn check_syntax<'a>(
+    path: &Path,
+    input: &'a str,
+    origin: Origin,
+    diagnostics: &mut Vec<Diagnostic>
+) -> Option<ContentTree<'a>> {
+    let (tree, errors) = content_tree::parse_recovering(input);
+
+    if errors.is_empty() {
+        return Some(tree);
+    }
+
+    for e in &errors {
+        diagnostics.push(Diagnostic::syntax(path, input, origin, e));
+    }
+
+    None
+}
+
+// Moves the warnings to 'warnings', and fails with the errors, sorted by file and line.
+fn fail_on_errors(diagnostics: Vec<Diagnostic>, warnings: &mut Vec<Diagnostic>) -> Result<(), Error> {
+    let (mut errors, mut w): (Vec<_>, Vec<_>) = diagnostics.into_iter().partition(|d| d.is_error());
+
+    warnings.append(&mut w);
+
+    if errors.is_empty() {
+        return Ok(());
+    }
+
+    errors.sort();
+
+    Err(Error::Diagnostics(errors))
+}
+
+// Adds the warnings of a failed build to its errors, sorted by file and line.
+fn with_warnings(e: Error, mut warnings: Vec<Diagnostic>) -> Error {
+    match e {
+        Error::Diagnostics(mut d) => {
+            d.append(&mut warnings);
+            d.sort();
+            d.dedup();
+            Error::Diagnostics(d)
+        },
+        e => e,
+    }
+}
+
+// Warns about the variables which the renderer couldn't resolve since the last call.
+fn warn_unresolved(renderer: &Renderer, path: &Path, warnings: &mut Vec<Diagnostic>) {
+    for name in renderer.take_unresolved() {
+        let d = Diagnostic::warning(path, None, format!("unknown variable '{}'", name));
+        warnings.push(d.with_code(error::UNKNOWN_VARIABLE));
+    }
+}
+
+// Returns the first of the templates which exists.
+fn find_template<'t, 'a>(
+    templates: &'t HashMap<&'a str, Program<'a>>,
+    names: &[&str]
+) -> Result<(&'t str, &'t Program<'a>), Error> {
+    for name in names {
+        if let Some((name, t)) = templates.get_key_value(*name) {
+            return Ok((*name, t));
         }
     }
+
+    Err(Error::MissingTemplate(names.join("' or '")))
+}
+
+// The 'page' variable of a page which has no content file.
+fn generated_page(title: &str, url: &str) -> Value {
+    let mut m = BTreeMap::new();
+    m.insert("title".to_owned(), Value::String(title.to_owned()));
+    m.insert("url".to_owned(), Value::String(url.to_owned()));
+
+    Value::Map(m)
+}
+
+// The modules and templates of the project, each read and parsed once, which 'Site' borrows.
+#[derive(Default)]
+struct Sources {
+    modules: HashMap<String, OwnedContentTree>,
+    // Flattened, and changed by the plugins
+    templates: HashMap<String, OwnedContentTree>,
+}
+
+// The project read and compiled once by 'Mold::prepared', to render pages
+// like 'Mold::render_str' and 'render_file' without reading the project again.
+pub struct Prepared<'m> {
+    mold: &'m Mold,
+    site: Site<'m>,
 }
 
-impl Default for Mold {
-    fn default() -> Self {
-        Self {
-            paths: Default::default(),
+impl Prepared<'_> {
+    // Renders a string like a page of the site, see 'Mold::render_str'.
+    pub fn render_str(&self, source: &str) -> Result<String, Error> {
+        let page = Page::parse(PathBuf::from("<string>"), PathBuf::new(), source)
+            .map_err(|e| Error::FrontMatter(PathBuf::from("<string>"), e))?;
+
+        self.render_page(page)
+    }
+
+    // Renders a file like a page of the site, see 'Mold::render_file'.
+    pub fn render_file<P: AsRef<Path>>(&self, path: P) -> Result<String, Error> {
+        let (fs, paths) = (self.mold.fs.as_ref(), &self.mold.paths);
+        let file = path.as_ref();
+
+        let source = fs.read_to_string(file)?;
+
+        let canonical = |p: &Path| fs.canonicalize(p).unwrap_or_else(|_| p.to_path_buf());
+        let path = paths.content.iter()
+            .find_map(|d| canonical(file).strip_prefix(canonical(d)).ok().map(Path::to_path_buf))
+            .unwrap_or_else(|| file.file_name().map(PathBuf::from).unwrap_or_default());
+
+        let page = Page::parse(file.to_path_buf(), path, &source)
+            .map_err(|e| Error::FrontMatter(file.to_path_buf(), e))?;
+
+        self.render_page(Page { modified: fs.modified(file), ..page })
+    }
+
+    // Renders a page which isn't one of the pages of the site, with the variables of the site.
+    // Plugins change the templates and the rendered page like in a build, but not the other pages,
+    // so the variables of the site don't reflect their changes. Since nothing is written,
+    // their hooks on the files of a build aren't called.
+    fn render_page(&self, mut page: Page) -> Result<String, Error> {
+        let (mold, site) = (self.mold, &self.site);
+
+        for plugin in &mold.plugins {
+            plugin.before_page(&mut page)?;
+        }
+
+        let mut ctx = mold.site_context(&site.collections, &site.pages, &site.taxonomies);
+
+        let compiled = page.compile(&site.templates, &site.renderer)?;
+
+        let mut out = String::new();
+        compiled.render(&site.templates, &site.renderer, &mut ctx, &mut out)?;
+
+        for plugin in &mold.plugins {
+            plugin.after_page(&page, &mut out)?;
         }
+
+        Ok(out)
     }
 }
 
+// The site ready to be rendered: the published pages with their taxonomies,
+// and the modules and templates compiled once, to be rendered for every page.
+struct Site<'s> {
+    pages: Vec<Page>,
+    // The collections of all pages, including unpublished ones, so that they exist even when empty
+    collections: Vec<String>,
+    taxonomies: Vec<Taxonomy>,
+    renderer: Renderer<'s, 's>,
+    templates: HashMap<&'s str, Program<'s>>,
+}
+
+#[derive(Default)]
 struct Paths {
     templates: Vec<PathBuf>,
     modules: Vec<PathBuf>,
@@ -62,25 +1027,20 @@ struct Paths {
     statics: Vec<PathBuf>,
     // Directory of built site
     output: PathBuf,
-    // Directory to store temporary build files
-    build: TempDir,
+    // Directory to store temporary build files, '.mold' next to the output unless it is set
+    build: Option<PathBuf>,
 }
 
-impl Default for Paths {
-    fn default() -> Self {
-        Self {
-            templates: Vec::new(),
-            modules: Vec::new(),
-            content: Vec::new(),
-            statics: Vec::new(),
-            output: PathBuf::new(),
-            build: Builder::new().prefix("mold").tempdir().unwrap(),
-        }
+impl Paths {
+    fn build(&self) -> PathBuf {
+        self.build.clone().unwrap_or_else(|| output::build_dir(&self.output))
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::convert::TryFrom;
+
     use super::*;
 
     #[test]
@@ -91,6 +1051,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn add_single_template_dir() {
         let mut m = Mold::new("out/");
 
@@ -101,6 +1062,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn add_single_modules_dir() {
         let mut m = Mold::new("out/");
 
@@ -111,6 +1073,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn add_single_content_dir() {
         let mut m = Mold::new("out/");
 
@@ -121,6 +1084,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::get_first)]
     fn add_single_static_dir() {
         let mut m = Mold::new("out/");
 
@@ -129,4 +1093,215 @@ mod tests {
         assert_eq!(1, m.paths.statics.len());
         assert_eq!(m.paths.statics.get(0), Some(&PathBuf::from("test/")));
     }
+
+    #[test]
+    fn builds_in_memory() {
+        let fs: MemoryFs = vec![
+            ("templates/base.html", "<title><<page.title>></title><<content>>"),
+            ("content/index.html", "---\ntitle: Home\n---\n<<base<<<<content<<Hi>>content>>>>base>>\n"),
+            ("static/css/style.css", "body {}"),
+        ].into_iter().collect();
+
+        let mut m = Mold::new("site");
+        m.set_file_system(fs.clone());
+        m.add_templates_dir("templates");
+        m.add_content_dir("content");
+        m.add_static_dir("static");
+
+        m.build().unwrap();
+
+        assert_eq!(fs.get("site/index.html"), Some(b"<title>Home</title>Hi".to_vec()));
+        assert_eq!(fs.get("site/css/style.css"), Some(b"body {}".to_vec()));
+        assert_eq!(fs.get(".mold/manifest"), Some(b"site\ncss/style.css\nindex.html\n".to_vec()));
+
+        // Building again replaces the output, without leaving the staged build behind.
+        m.build().unwrap();
+
+        assert_eq!(fs.files().iter().filter(|f| f.starts_with("site")).count(), 2);
+        assert!(fs.files().iter().all(|f| !f.starts_with(".mold/build-")));
+    }
+
+    #[test]
+    fn renders_a_single_page() {
+        let fs: MemoryFs = vec![
+            ("templates/mail.html", "<h1><<page.title>></h1><<content>><<footer>>"),
+            ("modules/footer.html", "<p><<site.title>></p>"),
+            ("content/posts/a.html", "---\ntitle: A\n---\n<<page.url>>"),
+            ("content/posts/b.html", "---\ntitle: B\n---\n"),
+            ("preview.html", "---\ntitle: Hi\n---\n<<mail<<<<content<<<<page.url>>>>content>>>>mail>>"),
+        ].into_iter().collect();
+
+        let mut m = Mold::new("site");
+        m.set_file_system(fs.clone());
+        m.set_title("Blog");
+        m.add_templates_dir("templates");
+        m.add_modules_dir("modules");
+        m.add_content_dir("content");
+
+        let out = m.render_str("<<for p in posts<<<<p.title>>>>for>> of <<site.title>>").unwrap();
+        assert_eq!(out, "AB of Blog");
+
+        let out = m.render_file("preview.html").unwrap();
+        assert_eq!(out, "<h1>Hi</h1>/preview.html<p>Blog</p>");
+
+        let out = m.render_file("content/posts/a.html").unwrap();
+        assert_eq!(out, "/posts/a.html");
+
+        // Nothing is built.
+        assert!(!fs.exists(Path::new("site")));
+
+        assert!(matches!(m.render_str("<<if>>"), Err(Error::Render(..))));
+
+        // The project is read once for all pages.
+        let out = m.prepared(|p| {
+            let first = p.render_str("<<for p in posts<<<<p.title>>>>for>>").unwrap();
+            fs.insert("content/posts/c.html", "---\ntitle: C\n---\n");
+            let second = p.render_str("<<for p in posts<<<<p.title>>>>for>>").unwrap();
+
+            (first, second)
+        });
+        assert_eq!(out.unwrap(), ("AB".to_owned(), "AB".to_owned()));
+        assert_eq!(m.render_str("<<for p in posts<<<<p.title>>>>for>>").unwrap(), "ABC");
+    }
+
+    #[test]
+    fn collects_errors_of_taxonomy_pages() {
+        let fs: MemoryFs = vec![
+            ("templates/taxonomy.html", "<<for t in missing<<>>for>>"),
+            ("templates/term.html", "<<term.name | nonsense>>"),
+            ("content/a.html", "---\ntags: [a]\n---\n<<if>>"),
+        ].into_iter().collect();
+
+        let mut m = Mold::new("site");
+        m.set_file_system(fs);
+        m.add_templates_dir("templates");
+        m.add_content_dir("content");
+        m.add_taxonomy("tags");
+
+        let errors: Vec<_> = m.check().unwrap().into_iter()
+            .map(|d| (d.path.to_string_lossy().into_owned(), d.code))
+            .collect();
+
+        assert!(errors.contains(&("content/a.html".to_owned(), Some("E022"))), "{:?}", errors);
+        assert!(errors.contains(&("taxonomy".to_owned(), Some("E020"))), "{:?}", errors);
+        assert!(errors.contains(&("term".to_owned(), Some("E026"))), "{:?}", errors);
+    }
+
+    #[test]
+    fn registered_tags() {
+        let mut m = Mold::new("site");
+        m.set_file_system(MemoryFs::new());
+        m.register_tag("version", |tag, _| Ok(format!("v{}", tag.args.trim())));
+
+        assert_eq!(m.render_str("<<version 1.2>>").unwrap(), "v1.2");
+    }
+
+    #[test]
+    fn plugins_hook_into_the_build() {
+        struct Shout;
+
+        impl Plugin for Shout {
+            fn templates_flattened(&self, templates: &mut HashMap<String, OwnedContentTree>) -> Result<(), Error> {
+                let base = ContentTree::try_from("<b><<content>></b>").unwrap().into_owned();
+                templates.insert("base".to_owned(), base);
+                Ok(())
+            }
+
+            fn before_page(&self, page: &mut Page) -> Result<(), Error> {
+                let title = page.front_matter.get("title").map(|t| t.to_string()).unwrap_or_default();
+                page.front_matter.insert("title".to_owned(), Value::String(title.to_uppercase()));
+                Ok(())
+            }
+
+            fn after_page(&self, _page: &Page, output: &mut String) -> Result<(), Error> {
+                output.push('!');
+                Ok(())
+            }
+
+            fn build_finished(&self, fs: &dyn FileSystem, output: &Path) -> Result<(), Error> {
+                fs.write(&output.join("done.txt"), b"done")?;
+                Ok(())
+            }
+        }
+
+        let fs: MemoryFs = vec![
+            ("templates/base.html", "<i><<content>></i>"),
+            ("content/posts/a.html", "---\ntitle: a\n---\n<<base<<<<content<<<<for p in posts<<<<p.title>>>>for>>>>content>>>>base>>"),
+        ].into_iter().collect();
+
+        let mut m = Mold::new("site");
+        m.set_file_system(fs.clone());
+        m.add_templates_dir("templates");
+        m.add_content_dir("content");
+        m.add_plugin(Shout);
+
+        m.build().unwrap();
+
+        assert_eq!(fs.get("site/posts/a.html"), Some(b"<b>A</b>!".to_vec()));
+        assert_eq!(fs.get("site/done.txt"), Some(b"done".to_vec()));
+
+        assert_eq!(m.render_str("<<base<<<<content<<x>>content>>>>base>>").unwrap(), "<b>x</b>!");
+    }
+
+    #[test]
+    fn failing_plugin_fails_the_build() {
+        struct Fail;
+
+        impl Plugin for Fail {
+            fn statics_copied(&self, _fs: &dyn FileSystem, _output: &Path) -> Result<(), Error> {
+                Err(Error::Plugin("no".to_owned()))
+            }
+        }
+
+        let fs: MemoryFs = vec![("content/index.html", "Hi")].into_iter().collect();
+
+        let mut m = Mold::new("site");
+        m.set_file_system(fs.clone());
+        m.add_content_dir("content");
+        m.add_plugin(Fail);
+
+        assert!(matches!(m.build(), Err(Error::Plugin(_))));
+
+        // The staged build is removed.
+        assert_eq!(fs.files(), vec![PathBuf::from("content/index.html")]);
+
+        // The hooks on the files of a build aren't called by 'check'.
+        assert_eq!(m.check().unwrap(), Vec::new());
+    }
+
+    #[test]
+    fn hooks_on_the_files_of_the_build() {
+        // Fails unless the files are there when the hooks are called.
+        struct Check;
+
+        impl Plugin for Check {
+            fn statics_copied(&self, fs: &dyn FileSystem, output: &Path) -> Result<(), Error> {
+                match fs.is_file(&output.join("css/style.css")) {
+                    true => Ok(()),
+                    false => Err(Error::Plugin("static files are missing".to_owned())),
+                }
+            }
+
+            fn build_finished(&self, fs: &dyn FileSystem, output: &Path) -> Result<(), Error> {
+                // Before the output directory is replaced
+                match fs.is_file(&output.join("index.html")) && !fs.exists(Path::new("site")) {
+                    true => Ok(()),
+                    false => Err(Error::Plugin("the output isn't finished".to_owned())),
+                }
+            }
+        }
+
+        let fs: MemoryFs = vec![
+            ("content/index.html", "Hi"),
+            ("static/css/style.css", "body {}"),
+        ].into_iter().collect();
+
+        let mut m = Mold::new("site");
+        m.set_file_system(fs);
+        m.add_content_dir("content");
+        m.add_static_dir("static");
+        m.add_plugin(Check);
+
+        m.build().unwrap();
+    }
 }
@@ -0,0 +1,303 @@
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process;
+
+use crate::error::Error;
+use crate::file_system::FileSystem;
+
+// Lists every file of a build, relative to the output directory, one per line, after a first
+// line with the output directory. It is kept in the build directory, so it isn't deployed.
+pub const MANIFEST: &str = "manifest";
+
+// The hidden directory next to the output, where builds are staged unless another is set.
+pub const CACHE_DIR: &str = ".mold";
+
+const STAGE_PREFIX: &str = "build-";
+
+// Creates an empty directory in 'build', the directory for temporary build files,
+// to build the site in. Unlike the system's temporary directory, 'build' must be on
+// the same file system as the output, so that the site can be renamed into place.
+// The caller removes it if the build fails.
+pub fn stage(fs: &dyn FileSystem, build: &Path) -> io::Result<PathBuf> {
+    fs.create_dir_all(build)?;
+
+    // Other builds may be staging in the same directory.
+    for n in 0.. {
+        let dir = build.join(format!("{}{}-{}", STAGE_PREFIX, process::id(), n));
+
+        match fs.create_dir(&dir) {
+            Ok(()) => return Ok(dir),
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    unreachable!()
+}
+
+// Writes the manifest of all files in the output directory into 'build'.
+// Since the output directory is replaced as a whole, files of earlier builds,
+// e.g. of a page which has been renamed, never linger. The manifest tells
+// which files a build produced, and that the output directory is one that Mold built.
+pub fn write_manifest(fs: &dyn FileSystem, output: &Path, build: &Path) -> io::Result<()> {
+    let mut files = Vec::new();
+
+    list_files(fs, output, output, &mut files)?;
+
+    files.sort();
+
+    let mut manifest = format!("{}\n", fs.canonicalize(output)?.display());
+    for f in files {
+        manifest.push_str(&f);
+        manifest.push('\n');
+    }
+
+    fs.write(&build.join(MANIFEST), manifest.as_bytes())
+}
+
+// Whether the manifest in 'build' is of the output directory, which must exist.
+fn is_built(fs: &dyn FileSystem, output: &Path, build: &Path) -> io::Result<bool> {
+    let manifest = match fs.read_to_string(&build.join(MANIFEST)) {
+        Ok(m) => m,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    Ok(manifest.lines().next() == Some(&*output.to_string_lossy()))
+}
+
+fn list_files(fs: &dyn FileSystem, root: &Path, dir: &Path, files: &mut Vec<String>) -> io::Result<()> {
+    for f in fs.read_dir(dir)? {
+        if f.is_dir {
+            list_files(fs, root, &f.path, files)?;
+        } else {
+            let parts: Vec<_> = f.path.strip_prefix(root).unwrap().iter().map(|p| p.to_string_lossy()).collect();
+            files.push(parts.join("/"));
+        }
+    }
+
+    Ok(())
+}
+
+// Removes the output directory, along with directories left behind in 'build' by interrupted builds,
+// and 'build' itself once it is empty.
+// Refuses to remove anything outside of 'root', the root of the project,
+// or an output directory without a manifest in 'build', since Mold didn't build it.
+pub fn clean(fs: &dyn FileSystem, output: &Path, build: &Path, root: &Path) -> Result<(), Error> {
+    let root = fs.canonicalize(root)?;
+
+    for dir in [parent_dir(output).as_path(), build] {
+        if fs.exists(dir) && !fs.canonicalize(dir)?.starts_with(&root) {
+            return Err(Error::RefusedToRemove(dir.to_path_buf(), "it is outside of the project"));
+        }
+    }
+
+    if fs.exists(output) {
+        let output = fs.canonicalize(output)?;
+
+        if output == root {
+            return Err(Error::RefusedToRemove(output, "it is the project itself"));
+        }
+
+        if !is_built(fs, &output, build)? {
+            return Err(Error::RefusedToRemove(output, "it has no manifest of a build"));
+        }
+
+        fs.remove_dir_all(&output)?;
+    }
+
+    if fs.is_file(&build.join(MANIFEST)) {
+        fs.remove_file(&build.join(MANIFEST))?;
+    }
+
+    // Nothing has been built yet.
+    if !fs.exists(build) {
+        return Ok(());
+    }
+
+    for f in fs.read_dir(build)? {
+        let name = f.path.file_name().unwrap_or_default().to_string_lossy();
+
+        if f.is_dir && name.starts_with(STAGE_PREFIX) {
+            fs.remove_dir_all(&f.path)?;
+        }
+    }
+
+    if fs.read_dir(build)?.is_empty() {
+        fs.remove_dir_all(build)?;
+    }
+
+    Ok(())
+}
+
+// Replaces the output directory with a staged directory, and removes the previous output.
+// Each step is a single rename: the previous output is moved aside to '<stage>.old', the staged
+// directory takes its place, and then the previous output is removed. Between the two renames,
+// the output directory doesn't exist. If the staged directory can't take its place, the previous
+// output is moved back, or else the error tells where it was left.
+pub fn replace(fs: &dyn FileSystem, stage: &Path, output: &Path) -> io::Result<()> {
+    if !fs.exists(output) {
+        if let Err(e) = fs.rename(stage, output) {
+            fs.remove_dir_all(stage)?;
+            return Err(e);
+        }
+
+        return Ok(());
+    }
+
+    let old = stage.with_extension("old");
+
+    if let Err(e) = fs.rename(output, &old) {
+        fs.remove_dir_all(stage)?;
+        return Err(e);
+    }
+
+    if let Err(e) = fs.rename(stage, output) {
+        if let Err(back) = fs.rename(&old, output) {
+            let message = format!("{}, and the previous output is left at '{}': {}", e, old.display(), back);
+            return Err(io::Error::new(back.kind(), message));
+        }
+
+        fs.remove_dir_all(stage)?;
+        return Err(e);
+    }
+
+    fs.remove_dir_all(&old)
+}
+
+// The directory where builds are staged by default, see 'CACHE_DIR'.
+pub fn build_dir(output: &Path) -> PathBuf {
+    parent_dir(output).join(CACHE_DIR)
+}
+
+// The directory which an output directory is in.
+fn parent_dir(path: &Path) -> PathBuf {
+    match path.parent() {
+        Some(p) if !p.as_os_str().is_empty() => p.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{create_dir_all, read_to_string, write};
+
+    use tempfile::tempdir;
+
+    use crate::file_system::DiskFs;
+
+    #[test]
+    fn replaces_existing_output() {
+        let root = tempdir().unwrap();
+        let output = root.path().join("output");
+
+        create_dir_all(&output).unwrap();
+        write(output.join("old.html"), "old").unwrap();
+
+        let stage = stage(&DiskFs, &build_dir(&output)).unwrap();
+        write(stage.join("new.html"), "new").unwrap();
+
+        replace(&DiskFs, &stage, &output).unwrap();
+
+        assert!(!output.join("old.html").exists());
+        assert_eq!(read_to_string(output.join("new.html")).unwrap(), "new");
+
+        // Nothing is left in the cache.
+        assert_eq!(build_dir(&output).read_dir().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn creates_missing_output() {
+        let root = tempdir().unwrap();
+        let output = root.path().join("a/b/output");
+
+        let stage = stage(&DiskFs, &build_dir(&output)).unwrap();
+        replace(&DiskFs, &stage, &output).unwrap();
+
+        assert!(output.is_dir());
+    }
+
+    #[test]
+    fn manifest_lists_all_files() {
+        let root = tempdir().unwrap();
+        let output = root.path().join("output");
+
+        create_dir_all(output.join("posts")).unwrap();
+        write(output.join("posts/a.html"), "").unwrap();
+        write(output.join("index.html"), "").unwrap();
+
+        create_dir_all(build_dir(&output)).unwrap();
+        write_manifest(&DiskFs, &output, &build_dir(&output)).unwrap();
+
+        let manifest = read_to_string(build_dir(&output).join(MANIFEST)).unwrap();
+        let expected = format!("{}\nindex.html\nposts/a.html\n", output.canonicalize().unwrap().display());
+        assert_eq!(manifest, expected);
+        assert!(!output.join(MANIFEST).exists());
+    }
+
+    #[test]
+    fn clean_removes_output_and_leftovers() {
+        let root = tempdir().unwrap();
+        let output = root.path().join("output");
+
+        create_dir_all(&output).unwrap();
+        create_dir_all(build_dir(&output)).unwrap();
+        write_manifest(&DiskFs, &output, &build_dir(&output)).unwrap();
+
+        // As if a build was interrupted.
+        stage(&DiskFs, &build_dir(&output)).unwrap();
+
+        clean(&DiskFs, &output, &build_dir(&output), root.path()).unwrap();
+
+        assert_eq!(root.path().read_dir().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn clean_refuses_unknown_directories() {
+        let root = tempdir().unwrap();
+        let content = root.path().join("content");
+
+        create_dir_all(&content).unwrap();
+
+        assert!(matches!(clean(&DiskFs, &content, root.path(), root.path()), Err(Error::RefusedToRemove(..))));
+        assert!(content.exists());
+    }
+
+    #[test]
+    fn clean_refuses_manifest_of_other_output() {
+        let root = tempdir().unwrap();
+        let output = root.path().join("output");
+        let other = root.path().join("other");
+
+        create_dir_all(&output).unwrap();
+        create_dir_all(&other).unwrap();
+        create_dir_all(build_dir(&output)).unwrap();
+        write_manifest(&DiskFs, &other, &build_dir(&output)).unwrap();
+
+        assert!(matches!(clean(&DiskFs, &output, &build_dir(&output), root.path()), Err(Error::RefusedToRemove(..))));
+        assert!(output.exists());
+    }
+
+    #[test]
+    fn clean_refuses_outside_of_project() {
+        let root = tempdir().unwrap();
+        let other = tempdir().unwrap();
+        let output = other.path().join("output");
+
+        create_dir_all(&output).unwrap();
+        create_dir_all(build_dir(&output)).unwrap();
+        write_manifest(&DiskFs, &output, &build_dir(&output)).unwrap();
+
+        assert!(matches!(clean(&DiskFs, &output, &build_dir(&output), root.path()), Err(Error::RefusedToRemove(..))));
+        assert!(output.exists());
+    }
+
+    #[test]
+    fn build_dir_next_to_output() {
+        assert_eq!(build_dir(Path::new("output/")), PathBuf::from("./.mold"));
+        assert_eq!(build_dir(Path::new("site/output")), PathBuf::from("site/.mold"));
+    }
+}
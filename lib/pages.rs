@@ -0,0 +1,321 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::SystemTime;
+
+use chrono::DateTime;
+use chrono::FixedOffset;
+use chrono::Utc;
+
+use crate::compile::Program;
+use crate::content_tree::ContentTree;
+use crate::context::Context;
+use crate::context::Value;
+use crate::date;
+use crate::diagnostic;
+use crate::diagnostic::Diagnostic;
+use crate::diagnostic::Origin;
+use crate::error::Error;
+use crate::error::FrontMatterError;
+use crate::file_system::FileSystem;
+use crate::front_matter;
+use crate::render::Renderer;
+use crate::templates;
+
+#[derive(Debug)]
+pub struct Page {
+    // The path of the source file
+    pub file: PathBuf,
+    // The path relative to the content directory, which is also used for the output
+    pub path: PathBuf,
+    pub front_matter: BTreeMap<String, Value>,
+    // Variables computed during the build, e.g. the terms of the page
+    pub variables: BTreeMap<String, Value>,
+    pub body: String,
+    // Where the body starts in the file, after the front matter
+    pub body_start: Origin,
+    // When the file was last changed, if the file system knows
+    pub modified: Option<SystemTime>,
+}
+
+impl Page {
+    // Splits the source of a page into its front matter and body, where 'file' is the
+    // path of the source and 'path' the one of the output.
+    pub fn parse(file: PathBuf, path: PathBuf, source: &str) -> Result<Page, FrontMatterError> {
+        let (front_matter, body) = front_matter::split(source)?;
+
+        let offset = source.len() - body.len();
+        let body_start = Origin {
+            line: diagnostic::line_of(source, offset),
+            offset,
+        };
+
+        Ok(Page {
+            file,
+            path,
+            front_matter,
+            variables: BTreeMap::new(),
+            body: body.to_owned(),
+            body_start,
+            modified: None,
+        })
+    }
+
+    pub fn url(&self) -> String {
+        let parts: Vec<_> = self.path.iter().map(|p| p.to_string_lossy()).collect();
+
+        format!("/{}", parts.join("/"))
+    }
+
+    // Pages in a subdirectory of the content directory belong to the collection of that name.
+    pub fn collection(&self) -> Option<String> {
+        let mut components = self.path.iter();
+        let first = components.next()?;
+
+        components.next()?;
+
+        Some(first.to_string_lossy().into_owned())
+    }
+
+    // The date in the front matter, if there is one and it is valid.
+    pub fn date(&self) -> Option<DateTime<FixedOffset>> {
+        date::parse(&self.front_matter.get("date")?.to_string())
+    }
+
+    pub fn is_draft(&self) -> bool {
+        self.front_matter.get("draft").is_some_and(|d| d.is_truthy())
+    }
+
+    // Whether the page is dated after 'now', i.e. scheduled to be published later.
+    pub fn is_scheduled(&self, now: DateTime<FixedOffset>) -> bool {
+        self.date().is_some_and(|d| d > now)
+    }
+
+    // When the page was last changed: the 'updated' or 'date' in the front matter,
+    // or else the modification time of its file.
+    pub fn lastmod(&self) -> Option<DateTime<FixedOffset>> {
+        let fm = self.front_matter.get("updated").or_else(|| self.front_matter.get("date"));
+
+        if let Some(d) = fm.and_then(|d| date::parse(&d.to_string())) {
+            return Some(d);
+        }
+
+        Some(DateTime::<Utc>::from(self.modified?).into())
+    }
+
+    // The 'page' variable: the front matter together with the url of the page
+    // and the variables computed during the build.
+    pub fn to_value(&self) -> Value {
+        let mut m = self.front_matter.clone();
+
+        m.insert("url".to_owned(), Value::String(self.url()));
+
+        for (k, v) in &self.variables {
+            m.insert(k.clone(), v.clone());
+        }
+
+        Value::Map(m)
+    }
+
+    // Parses and compiles the body once, so that it can be rendered for the page and its feed.
+    pub fn compile<'p>(&'p self, templates: &HashMap<&str, Program>, renderer: &Renderer) -> Result<CompiledPage<'p>, Error> {
+        let tree = ContentTree::try_from(self.body.as_ref())
+            .map_err(|e| Error::Parse(self.file.clone(), e))?;
+
+        let template = templates::parent_tag(&tree)
+            .filter(|tag| templates.contains_key(tag.name));
+
+        Ok(match template {
+            Some(tag) => CompiledPage {
+                page: self,
+                template: Some(tag.name),
+                program: renderer.compile(&tag.content),
+            },
+            None => CompiledPage {
+                page: self,
+                template: None,
+                program: renderer.compile(&tree),
+            },
+        })
+    }
+}
+
+// A page with its body compiled, see 'Page::compile'.
+pub struct CompiledPage<'p> {
+    pub page: &'p Page,
+    // The template which the page extends, if any
+    template: Option<&'p str>,
+    // The blocks filling in the template, or else the whole body
+    program: Program<'p>,
+}
+
+impl CompiledPage<'_> {
+    // Renders the page into 'out', using the template it extends, if any.
+    pub fn render(
+        &self,
+        templates: &HashMap<&str, Program>,
+        renderer: &Renderer,
+        ctx: &mut Context,
+        out: &mut String
+    ) -> Result<(), Error> {
+        ctx.push_scope();
+        ctx.insert("page", self.page.to_value());
+
+        out.clear();
+
+        // The blocks of the page fill in those of the template.
+        let result = match self.template.and_then(|t| templates.get(t)) {
+            Some(template) => renderer.render_into(out, template, Some(&self.program), ctx),
+            None => renderer.render_into(out, &self.program, None, ctx),
+        };
+
+        ctx.pop_scope();
+
+        result.map_err(|e| Error::Render(self.page.file.clone(), e))
+    }
+
+    // Renders only what the page puts in the 'content' block of its template,
+    // or the whole page if it doesn't extend a template.
+    pub fn render_content(&self, renderer: &Renderer, ctx: &mut Context) -> Result<String, Error> {
+        ctx.push_scope();
+        ctx.insert("page", self.page.to_value());
+
+        let mut out = String::new();
+
+        let result = match self.template {
+            Some(_) => renderer.render_block_into(&mut out, &self.program, "content", ctx),
+            None => Ok(false),
+        }.and_then(|found| match found {
+            true => Ok(()),
+            false => renderer.render_into(&mut out, &self.program, None, ctx),
+        });
+
+        ctx.pop_scope();
+
+        result.map(|()| out).map_err(|e| Error::Render(self.page.file.clone(), e))
+    }
+}
+
+// Reads all pages in a content directory, including its subdirectories.
+// Pages with invalid front matter are left out, and added to 'diagnostics' instead.
+pub fn read_pages<D: AsRef<Path>>(
+    fs: &dyn FileSystem,
+    dir: D,
+    diagnostics: &mut Vec<Diagnostic>
+) -> io::Result<Vec<Page>> {
+    let dir = dir.as_ref();
+
+    let mut pages = Vec::new();
+
+    read_pages_recursive(fs, dir, dir, &mut pages, diagnostics)?;
+
+    pages.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(pages)
+}
+
+fn read_pages_recursive(
+    fs: &dyn FileSystem,
+    root: &Path,
+    dir: &Path,
+    pages: &mut Vec<Page>,
+    diagnostics: &mut Vec<Diagnostic>
+) -> io::Result<()> {
+    for f in fs.read_dir(dir)? {
+        let file = f.path;
+
+        if f.is_dir {
+            read_pages_recursive(fs, root, &file, pages, diagnostics)?;
+            continue;
+        }
+
+        let source = fs.read_to_string(&file)?;
+
+        let path = file.strip_prefix(root).unwrap().to_path_buf();
+        let modified = fs.modified(&file);
+
+        match Page::parse(file.clone(), path, &source) {
+            Ok(page) => pages.push(Page { modified, ..page }),
+            Err(e) => diagnostics.push(Diagnostic::front_matter(file, Some(&source), e)),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::filters::Filters;
+    use crate::tags::Tags;
+
+    fn page(path: &str) -> Page {
+        Page {
+            file: Path::new("content").join(path),
+            path: PathBuf::from(path),
+            front_matter: BTreeMap::new(),
+            variables: BTreeMap::new(),
+            body: String::new(),
+            body_start: Origin::START,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn url_of_nested_page() {
+        assert_eq!(page("posts/hello.html").url(), "/posts/hello.html");
+    }
+
+    #[test]
+    fn collection_of_nested_page() {
+        assert_eq!(page("index.html").collection(), None);
+        assert_eq!(page("posts/hello.html").collection(), Some("posts".to_owned()));
+    }
+
+    #[test]
+    fn drafts_and_scheduled_pages() {
+        let mut p = page("posts/hello.html");
+        let now = date::parse("2020-10-01").unwrap();
+
+        assert!(!p.is_draft());
+        assert!(!p.is_scheduled(now));
+
+        p.front_matter.insert("draft".to_owned(), Value::Bool(true));
+        p.front_matter.insert("date".to_owned(), Value::String("2020-10-02".to_owned()));
+
+        assert!(p.is_draft());
+        assert!(p.is_scheduled(now));
+    }
+
+    #[test]
+    fn renders_with_template() {
+        let base = ContentTree::try_from("<title><<page.title>></title><<content>>").unwrap();
+
+        let mut p = page("index.html");
+        p.front_matter.insert("title".to_owned(), Value::String("Home".to_owned()));
+        p.body = "<<base<<<<content<<Hi>>content>>>>base>>\n".to_owned();
+
+        let modules = HashMap::new();
+        let filters = Filters::new();
+        let tags = Tags::new();
+        let renderer = Renderer::new(&modules, &filters, &tags);
+
+        let mut templates = HashMap::new();
+        templates.insert("base", renderer.compile(&base));
+
+        let compiled = p.compile(&templates, &renderer).unwrap();
+
+        let mut out = "from the last page".to_owned();
+        compiled.render(&templates, &renderer, &mut Context::new(), &mut out).unwrap();
+
+        assert_eq!(out, "<title>Home</title>Hi");
+
+        let out = compiled.render_content(&renderer, &mut Context::new()).unwrap();
+
+        assert_eq!(out, "Hi");
+    }
+}
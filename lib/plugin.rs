@@ -0,0 +1,47 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::content_tree::OwnedContentTree;
+use crate::error::Error;
+use crate::file_system::FileSystem;
+use crate::pages::Page;
+
+// Hooks into the build of a site, e.g. to minify pages or add files to the output,
+// see 'Mold::add_plugin'. The hooks are called in this order, and do nothing unless
+// they are implemented. An error fails the build.
+//
+// 'Mold::check' calls the hooks on the templates and pages, but not those on the files
+// of a build, 'statics_copied' and 'build_finished'.
+//
+// Plugins are shared by builds, so state such as hashes of the output
+// needs interior mutability.
+pub trait Plugin {
+    // After the templates have been flattened, before they are used for any page.
+    fn templates_flattened(&self, _templates: &mut HashMap<String, OwnedContentTree>) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // Before the pages are rendered, for every published page of the content directories.
+    // Since this is before the collections are made, changes to the front matter show up in them.
+    // 'Mold::render_str' and 'render_file' only call it for the page they render.
+    fn before_page(&self, _page: &mut Page) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // After a page has been rendered, before its output is written.
+    fn after_page(&self, _page: &Page, _output: &mut String) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // After the static files have been copied to 'output', the directory the site is built in.
+    // This isn't the output directory itself, which is only replaced once the build is done.
+    fn statics_copied(&self, _fs: &dyn FileSystem, _output: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+
+    // After everything has been built into 'output', see 'statics_copied', right before it
+    // replaces the output directory. On an error the previous output is kept.
+    fn build_finished(&self, _fs: &dyn FileSystem, _output: &Path) -> Result<(), Error> {
+        Ok(())
+    }
+}
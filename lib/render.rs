@@ -0,0 +1,599 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::compile::Instr;
+use crate::compile::Program;
+use crate::compile::TagKind;
+use crate::content_tree::ContentTree;
+use crate::context::Context;
+use crate::context::Value;
+use crate::error::RenderError;
+use crate::filters::Filters;
+use crate::tags::Tags;
+
+// How deep modules may be nested inside each other before we give up.
+const MAX_DEPTH: usize = 32;
+
+// The contents filling in the blocks of a program, by name.
+type Fills<'p, 'a> = [(&'a str, &'p [Instr<'a>])];
+
+// Renders a (flattened) content tree into its final output.
+// Trees are compiled into programs first, see 'Program', which templates and
+// modules are once, so that they can be rendered for every page.
+//
+// Tags are resolved in the following order:
+//  1. 'for' and 'if' are evaluated as control flow, which custom tags can't replace.
+//  2. A custom tag is replaced by the output of its handler, see 'Tags'.
+//     It takes precedence over a module with the same name.
+//  3. A tag with the name of a module is replaced by the module,
+//     with the content of the tag placed in the '<<content>>' of the module.
+//  4. A self-closing tag with the name of a variable is replaced by its value,
+//     passed through the filters following it, e.g. '<<page.title | upper>>'.
+//  5. Any other tag is a block which wasn't filled in, so its content is used.
+pub struct Renderer<'r, 'a> {
+    modules: HashMap<&'a str, Program<'a>>,
+    pub filters: &'r Filters,
+    pub tags: &'r Tags,
+    // Fields of variables which were referred to but don't exist, e.g. 'page.titel'.
+    unresolved: RefCell<Vec<String>>,
+}
+
+impl<'r, 'a> Renderer<'r, 'a> {
+    pub fn new(
+        modules: &HashMap<&'a str, ContentTree<'a>>,
+        filters: &'r Filters,
+        tags: &'r Tags
+    ) -> Renderer<'r, 'a> {
+        let modules = modules.iter()
+            .map(|(name, tree)| (*name, Program::compile(tree, |n| tag_kind(n, |m| modules.contains_key(m), tags))))
+            .collect();
+
+        Renderer {
+            modules,
+            filters,
+            tags,
+            unresolved: RefCell::new(Vec::new()),
+        }
+    }
+
+    // Compiles a tree, where tags with the name of a module or custom tag are those.
+    pub fn compile<'t>(&self, tree: &ContentTree<'t>) -> Program<'t> {
+        Program::compile(tree, |name| tag_kind(name, |m| self.modules.contains_key(m), self.tags))
+    }
+
+    // Returns the variables which couldn't be resolved since the last call.
+    pub fn take_unresolved(&self) -> Vec<String> {
+        self.unresolved.take()
+    }
+
+    pub fn render(&self, tree: &ContentTree, ctx: &mut Context) -> Result<String, RenderError> {
+        let mut out = String::new();
+
+        self.render_into(&mut out, &self.compile(tree), None, ctx)?;
+
+        Ok(out)
+    }
+
+    // Renders the program at the end of 'out', e.g. a buffer which is reused for every page.
+    // The blocks at the top of 'blocks', if any, fill in the blocks of the program with the same name,
+    // e.g. the blocks of a page extending the template.
+    pub fn render_into(
+        &self,
+        out: &mut String,
+        program: &Program,
+        blocks: Option<&Program>,
+        ctx: &mut Context
+    ) -> Result<(), RenderError> {
+        let fills = blocks.map(|b| b.blocks()).unwrap_or_default();
+
+        self.render_program(out, program, &fills, ctx, 0)
+    }
+
+    // Renders the content of the first block of the program with the name at the end of 'out',
+    // e.g. what a page puts in the 'content' block of its template. Returns whether there is one.
+    pub fn render_block_into(
+        &self,
+        out: &mut String,
+        program: &Program,
+        name: &str,
+        ctx: &mut Context
+    ) -> Result<bool, RenderError> {
+        match program.blocks().into_iter().find(|(n, _)| *n == name) {
+            Some((_, code)) => self.render_code(out, code, ctx, 0).map(|()| true),
+            None => Ok(false),
+        }
+    }
+
+    fn render_program<'p>(
+        &self,
+        out: &mut String,
+        program: &'p Program<'p>,
+        fills: &Fills<'p, 'p>,
+        ctx: &mut Context,
+        depth: usize
+    ) -> Result<(), RenderError> {
+        let code = &program.code;
+
+        let mut i = 0;
+        while i < code.len() {
+            let instr = &code[i];
+
+            let own = &code[i + 1..i + 1 + instr.content_len()];
+
+            // Only the last of the contents filling in a block is used, like the last of the blocks.
+            let content = instr.name()
+                .filter(|name| program.is_slot(i, name))
+                .and_then(|name| fills.iter().rev().find(|(n, _)| *n == name))
+                .map_or(own, |(_, content)| *content);
+
+            self.render_instr(out, instr, content, &code[i + 1 + own.len()..i + 1 + instr.size()], ctx, depth)?;
+
+            i += 1 + instr.size();
+        }
+
+        Ok(())
+    }
+
+    fn render_code(&self, out: &mut String, code: &[Instr], ctx: &mut Context, depth: usize) -> Result<(), RenderError> {
+        let mut i = 0;
+        while i < code.len() {
+            let instr = &code[i];
+
+            let content = &code[i + 1..i + 1 + instr.content_len()];
+            let rest = &code[i + 1 + content.len()..i + 1 + instr.size()];
+
+            self.render_instr(out, instr, content, rest, ctx, depth)?;
+
+            i += 1 + instr.size();
+        }
+
+        Ok(())
+    }
+
+    // Renders an instruction with its content, and 'rest' of what follows it,
+    // i.e. the 'else' of a loop or the branches of an 'if'.
+    fn render_instr(
+        &self,
+        out: &mut String,
+        instr: &Instr,
+        content: &[Instr],
+        rest: &[Instr],
+        ctx: &mut Context,
+        depth: usize
+    ) -> Result<(), RenderError> {
+        match instr {
+            Instr::Text(text) => out.push_str(text),
+            Instr::Tag { name, args, filters, .. } => {
+                if content.is_empty() {
+                    if args.starts_with('|') {
+                        let filters = filters.as_ref().ok_or_else(|| RenderError::InvalidFilter((*args).to_owned()))?;
+                        let value = self.apply_filters(name, filters, ctx)?;
+                        write!(out, "{}", value).unwrap();
+                        return Ok(());
+                    }
+
+                    if let Some(value) = ctx.lookup(name) {
+                        write!(out, "{}", value).unwrap();
+                        return Ok(());
+                    }
+
+                    // Other names may be blocks which weren't filled in.
+                    if name.contains('.') {
+                        let mut unresolved = self.unresolved.borrow_mut();
+
+                        if !unresolved.iter().any(|n| n == name) {
+                            unresolved.push((*name).to_owned());
+                        }
+                    }
+                }
+
+                self.render_code(out, content, ctx, depth)?;
+            },
+            Instr::Module { name, .. } => {
+                if depth >= MAX_DEPTH {
+                    return Err(RenderError::ModuleRecursion((*name).to_owned()));
+                }
+
+                let module = &self.modules[name];
+
+                self.render_program(out, module, &[("content", content)], ctx, depth + 1)?;
+            },
+            Instr::Custom(tag) => {
+                let handler = self.tags.get(tag.name).unwrap();
+
+                let output = handler(tag, ctx)
+                    .map_err(|e| RenderError::Tag(tag.name.to_owned(), e))?;
+
+                out.push_str(&output);
+            },
+            Instr::For { item, collection, .. } => self.render_for(out, item, collection, content, rest, ctx, depth)?,
+            Instr::If { condition, .. } => self.render_if(out, condition, content, rest, ctx, depth)?,
+            // Branches are rendered by their 'if'.
+            Instr::Branch { .. } => unreachable!(),
+            Instr::Fail { error, .. } => return Err(error.clone()),
+        }
+
+        Ok(())
+    }
+
+    // A missing variable is passed to the filters as an empty string,
+    // so that it can be replaced using 'default'.
+    fn apply_filters(&self, name: &str, pipeline: &[(&str, Vec<Value>)], ctx: &Context) -> Result<Value, RenderError> {
+        let mut value = ctx.lookup(name).cloned()
+            .unwrap_or_else(|| Value::String(String::new()));
+
+        for (name, args) in pipeline {
+            let filter = self.filters.get(name)
+                .ok_or_else(|| RenderError::UnknownFilter((*name).to_owned()))?;
+
+            value = filter(&value, args)
+                .map_err(|e| RenderError::Filter((*name).to_owned(), e))?;
+        }
+
+        Ok(value)
+    }
+
+    // <<for item in collection<<...>>else<<...>>for>>
+    // The 'else' content is used when the collection is empty.
+    #[allow(clippy::too_many_arguments)]
+    fn render_for(
+        &self,
+        out: &mut String,
+        item: &str,
+        collection: &str,
+        content: &[Instr],
+        otherwise: &[Instr],
+        ctx: &mut Context,
+        depth: usize
+    ) -> Result<(), RenderError> {
+        let items = match ctx.lookup(collection) {
+            Some(Value::List(items)) => items.clone(),
+            _ => return Err(RenderError::UnknownCollection(collection.to_owned())),
+        };
+
+        if items.is_empty() {
+            return self.render_code(out, otherwise, ctx, depth);
+        }
+
+        for value in items {
+            ctx.push_scope();
+            ctx.insert(item, value);
+
+            let result = self.render_code(out, content, ctx, depth);
+
+            ctx.pop_scope();
+
+            result?;
+        }
+
+        Ok(())
+    }
+
+    // <<if cond<<...>>elif cond<<...>>else<<...>>if>>
+    // A condition is a variable, optionally preceded by 'not'.
+    fn render_if(
+        &self,
+        out: &mut String,
+        condition: &str,
+        content: &[Instr],
+        branches: &[Instr],
+        ctx: &mut Context,
+        depth: usize
+    ) -> Result<(), RenderError> {
+        if evaluate(condition, ctx)? {
+            return self.render_code(out, content, ctx, depth);
+        }
+
+        let mut i = 0;
+        while i < branches.len() {
+            let (name, condition, len) = match &branches[i] {
+                Instr::Branch { name, condition, len } => (*name, *condition, *len),
+                _ => unreachable!(),
+            };
+
+            let content = &branches[i + 1..i + 1 + len];
+            let last = i + 1 + len == branches.len();
+
+            let taken = match name {
+                "elif" => evaluate(condition, ctx)?,
+                "else" if last => true,
+                _ => return Err(RenderError::UnexpectedBranch(name.to_owned())),
+            };
+
+            if taken {
+                return self.render_code(out, content, ctx, depth);
+            }
+
+            i += 1 + len;
+        }
+
+        Ok(())
+    }
+}
+
+// Custom tags take precedence over modules, see 'Renderer'.
+fn tag_kind<F: Fn(&str) -> bool>(name: &str, is_module: F, tags: &Tags) -> TagKind {
+    if tags.contains(name) {
+        TagKind::Custom
+    } else if is_module(name) {
+        TagKind::Module
+    } else {
+        TagKind::Other
+    }
+}
+
+fn evaluate(condition: &str, ctx: &Context) -> Result<bool, RenderError> {
+    let (negated, variable) = match condition.strip_prefix("not ") {
+        Some(v) => (true, v.trim()),
+        None => (false, condition.trim()),
+    };
+
+    if variable.is_empty() {
+        return Err(RenderError::InvalidCondition);
+    }
+
+    let truthy = ctx.lookup(variable).is_some_and(|v| v.is_truthy());
+
+    Ok(truthy != negated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::collections::BTreeMap;
+    use std::convert::TryFrom;
+
+    fn s(s: &str) -> Value {
+        Value::String(s.to_owned())
+    }
+
+    fn post(title: &str, draft: bool) -> Value {
+        let mut m = BTreeMap::new();
+        m.insert("title".to_owned(), s(title));
+        m.insert("draft".to_owned(), Value::Bool(draft));
+        Value::Map(m)
+    }
+
+    fn render_str(input: &str, ctx: &mut Context) -> Result<String, RenderError> {
+        let tree = ContentTree::try_from(input).unwrap();
+
+        let modules = HashMap::new();
+        let filters = Filters::new();
+        let tags = Tags::new();
+        let renderer = Renderer::new(&modules, &filters, &tags);
+
+        renderer.render(&tree, ctx)
+    }
+
+    #[test]
+    fn variables_are_replaced() {
+        let mut ctx = Context::new();
+        ctx.insert("page", post("Hello", false));
+
+        assert_eq!(render_str("<h1><<page.title>></h1>", &mut ctx).unwrap(), "<h1>Hello</h1>");
+    }
+
+    #[test]
+    fn unresolved_variables() {
+        let tree = ContentTree::try_from("<<page.titel>><<page.titel>><<page.title>><<title>>").unwrap();
+
+        let modules = HashMap::new();
+        let filters = Filters::new();
+        let tags = Tags::new();
+        let renderer = Renderer::new(&modules, &filters, &tags);
+
+        let mut ctx = Context::new();
+        ctx.insert("page", post("Hello", false));
+
+        renderer.render(&tree, &mut ctx).unwrap();
+
+        assert_eq!(renderer.take_unresolved(), vec!["page.titel".to_owned()]);
+        assert!(renderer.take_unresolved().is_empty());
+    }
+
+    #[test]
+    fn unfilled_blocks_use_their_content() {
+        let mut ctx = Context::new();
+
+        assert_eq!(render_str("<<title<<My Website>>title>>", &mut ctx).unwrap(), "My Website");
+        assert_eq!(render_str("a<<content>>b", &mut ctx).unwrap(), "ab");
+    }
+
+    #[test]
+    fn for_loop() {
+        let mut ctx = Context::new();
+        ctx.insert("posts", Value::List(vec![post("a", false), post("b", false)]));
+
+        let out = render_str("<<for post in posts<<[<<post.title>>]>>for>>", &mut ctx).unwrap();
+
+        assert_eq!(out, "[a][b]");
+        assert_eq!(ctx.lookup("post"), None);
+    }
+
+    #[test]
+    fn for_loop_else_on_empty_collection() {
+        let mut ctx = Context::new();
+        ctx.insert("posts", Value::List(Vec::new()));
+
+        let out = render_str("<<for post in posts<<x>>else<<No posts>>for>>", &mut ctx).unwrap();
+
+        assert_eq!(out, "No posts");
+    }
+
+    #[test]
+    fn unknown_collection() {
+        let mut ctx = Context::new();
+
+        let out = render_str("<<for post in posts<<x>>for>>", &mut ctx);
+
+        assert_eq!(out, Err(RenderError::UnknownCollection("posts".to_owned())));
+    }
+
+    #[test]
+    fn invalid_loop() {
+        let mut ctx = Context::new();
+
+        let out = render_str("<<for posts<<x>>for>>", &mut ctx);
+
+        assert_eq!(out, Err(RenderError::InvalidLoop("posts".to_owned())));
+    }
+
+    #[test]
+    fn if_else() {
+        let mut ctx = Context::new();
+        ctx.insert("page", post("a", true));
+
+        let i = "<<if page.draft<<Draft>>else<<Published>>if>>";
+        assert_eq!(render_str(i, &mut ctx).unwrap(), "Draft");
+
+        let i = "<<if not page.draft<<Published>>elif page.title<<Titled>>if>>";
+        assert_eq!(render_str(i, &mut ctx).unwrap(), "Titled");
+
+        let i = "<<if page.missing<<x>>if>>";
+        assert_eq!(render_str(i, &mut ctx).unwrap(), "");
+    }
+
+    #[test]
+    fn if_without_condition() {
+        let mut ctx = Context::new();
+
+        assert_eq!(render_str("<<if<<x>>if>>", &mut ctx), Err(RenderError::InvalidCondition));
+    }
+
+    #[test]
+    fn unexpected_branch() {
+        let mut ctx = Context::new();
+
+        let out = render_str("<<foo<<x>>else<<y>>foo>>", &mut ctx);
+
+        assert_eq!(out, Err(RenderError::UnexpectedBranch("else".to_owned())));
+    }
+
+    #[test]
+    fn modules_are_expanded() {
+        let quote = ContentTree::try_from("<blockquote><<content>></blockquote>").unwrap();
+
+        let mut modules = HashMap::new();
+        modules.insert("quote", quote);
+
+        let tree = ContentTree::try_from("<<quote<<Hi>>quote>>").unwrap();
+
+        let filters = Filters::new();
+        let tags = Tags::new();
+        let renderer = Renderer::new(&modules, &filters, &tags);
+
+        let out = renderer.render(&tree, &mut Context::new()).unwrap();
+
+        assert_eq!(out, "<blockquote>Hi</blockquote>");
+    }
+
+    #[test]
+    fn custom_tags_call_their_handlers() {
+        // A module in a module, which the custom tag replaces.
+        let mut modules = HashMap::new();
+        modules.insert("card", ContentTree::try_from("[<<shout<<<<name>>>>shout>>]").unwrap());
+        modules.insert("shout", ContentTree::try_from("<<content>>").unwrap());
+
+        let mut tags = Tags::new();
+        tags.insert("shout", |tag, ctx| {
+            let name = ctx.lookup("name").ok_or("no name")?;
+            Ok(format!("{}{}!", tag.content, name).to_uppercase())
+        });
+        tags.insert("fail", |tag, _| Err(format!("args '{}'", tag.args)));
+
+        let filters = Filters::new();
+        let renderer = Renderer::new(&modules, &filters, &tags);
+
+        let mut ctx = Context::new();
+        ctx.insert("name", s("mold"));
+
+        let tree = ContentTree::try_from("<<card>>").unwrap();
+        assert_eq!(renderer.render(&tree, &mut ctx).unwrap(), "[<<NAME>>MOLD!]");
+
+        let tree = ContentTree::try_from("<<fail x>>").unwrap();
+        let err = renderer.render(&tree, &mut ctx);
+        assert_eq!(err, Err(RenderError::Tag("fail".to_owned(), "args 'x'".to_owned())));
+    }
+
+    #[test]
+    fn blocks_fill_in_compiled_template() {
+        let quote = ContentTree::try_from("<blockquote><<content>></blockquote>").unwrap();
+
+        let mut modules = HashMap::new();
+        modules.insert("quote", quote);
+
+        let filters = Filters::new();
+        let tags = Tags::new();
+        let renderer = Renderer::new(&modules, &filters, &tags);
+
+        let base = ContentTree::try_from("<<title<<Untitled>>title>>: <<content>>").unwrap();
+        let template = renderer.compile(&base);
+
+        let mut ctx = Context::new();
+        let mut out = String::new();
+
+        for (page, expected) in &[
+            ("<<title<<A>>title>><<content<<<<quote<<a>>quote>>>>content>>", "A: <blockquote>a</blockquote>"),
+            ("<<content<<b>>content>>", "Untitled: b"),
+        ] {
+            let page = ContentTree::try_from(*page).unwrap();
+            let blocks = renderer.compile(&page);
+
+            out.clear();
+            renderer.render_into(&mut out, &template, Some(&blocks), &mut ctx).unwrap();
+
+            assert_eq!(out, *expected);
+        }
+    }
+
+    #[test]
+    fn recursive_module() {
+        let quote = ContentTree::try_from("<<quote>>").unwrap();
+
+        let mut modules = HashMap::new();
+        modules.insert("quote", quote);
+
+        let tree = ContentTree::try_from("<<quote>>").unwrap();
+
+        let filters = Filters::new();
+        let tags = Tags::new();
+        let renderer = Renderer::new(&modules, &filters, &tags);
+
+        let out = renderer.render(&tree, &mut Context::new());
+
+        assert_eq!(out, Err(RenderError::ModuleRecursion("quote".to_owned())));
+    }
+
+    #[test]
+    fn filters_are_applied() {
+        let mut ctx = Context::new();
+        ctx.insert("page", post("Hello World", false));
+
+        let out = render_str("<<page.title | slugify | upper>>", &mut ctx);
+        assert_eq!(out, Ok("HELLO-WORLD".to_owned()));
+
+        let out = render_str("<<page.missing | default \"none\">>", &mut ctx);
+        assert_eq!(out, Ok("none".to_owned()));
+    }
+
+    #[test]
+    fn unknown_filter() {
+        let mut ctx = Context::new();
+
+        let out = render_str("<<page.title | shout>>", &mut ctx);
+
+        assert_eq!(out, Err(RenderError::UnknownFilter("shout".to_owned())));
+    }
+
+    #[test]
+    fn failing_filter() {
+        let mut ctx = Context::new();
+        ctx.insert("page", post("Hello", false));
+
+        let out = render_str("<<page.title | date>>", &mut ctx);
+
+        assert_eq!(out, Err(RenderError::Filter("date".to_owned(), "'Hello' is not a date".to_owned())));
+    }
+}
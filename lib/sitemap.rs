@@ -0,0 +1,73 @@
+use std::fmt::Write;
+
+use chrono::DateTime;
+use chrono::FixedOffset;
+
+use crate::filters::escape;
+
+#[derive(Debug)]
+pub struct Entry {
+    // The url of the page, relative to the base url
+    pub url: String,
+    pub lastmod: Option<DateTime<FixedOffset>>,
+}
+
+// See https://www.sitemaps.org/protocol.html
+pub fn sitemap(base_url: &str, entries: &[Entry]) -> String {
+    let base_url = base_url.trim_end_matches('/');
+
+    let mut out = String::new();
+
+    out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    out.push_str("<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n");
+
+    for e in entries {
+        out.push_str("<url>\n");
+        writeln!(out, "<loc>{}{}</loc>", escape(base_url), escape(&e.url)).unwrap();
+
+        if let Some(lastmod) = e.lastmod {
+            writeln!(out, "<lastmod>{}</lastmod>", lastmod.to_rfc3339()).unwrap();
+        }
+
+        out.push_str("</url>\n");
+    }
+
+    out.push_str("</urlset>\n");
+
+    out
+}
+
+pub fn robots(sitemap_url: &str) -> String {
+    format!("User-agent: *\nAllow: /\n\nSitemap: {}\n", sitemap_url)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::date;
+
+    #[test]
+    fn urls_with_and_without_lastmod() {
+        let entries = vec![
+            Entry {
+                url: "/a.html?x=1&y=2".to_owned(),
+                lastmod: date::parse("2020-10-01"),
+            },
+            Entry {
+                url: "/tags/".to_owned(),
+                lastmod: None,
+            },
+        ];
+
+        let s = sitemap("https://example.com/", &entries);
+
+        assert!(s.contains("<loc>https://example.com/a.html?x=1&amp;y=2</loc>\n<lastmod>2020-10-01T00:00:00+00:00</lastmod>"));
+        assert!(s.contains("<loc>https://example.com/tags/</loc>\n</url>"));
+    }
+
+    #[test]
+    fn robots_refers_to_sitemap() {
+        assert!(robots("https://example.com/sitemap.xml").ends_with("Sitemap: https://example.com/sitemap.xml\n"));
+    }
+}
@@ -0,0 +1,44 @@
+use std::io;
+use std::path::Path;
+use std::path::PathBuf;
+
+use crate::file_system::FileSystem;
+
+// A template or module, which is referred to by the name of its file.
+#[derive(Debug)]
+pub struct SourceFile {
+    pub name: String,
+    pub path: PathBuf,
+    pub content: String,
+}
+
+// Reads all files in a directory, but not in its subdirectories.
+pub fn read_dir<D: AsRef<Path>>(fs: &dyn FileSystem, dir: D) -> io::Result<Vec<SourceFile>> {
+    let mut files = Vec::new();
+
+    for f in fs.read_dir(dir.as_ref())? {
+        if f.is_dir {
+            continue;
+        }
+
+        let path = f.path;
+
+        let name = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(name) => name.to_owned(),
+            None => continue,
+        };
+
+        let content = fs.read_to_string(&path)?;
+
+        files.push(SourceFile {
+            name,
+            path,
+            content,
+        });
+    }
+
+    // Keep the output independent of the order of the file system.
+    files.sort_by(|a, b| a.path.cmp(&b.path));
+
+    Ok(files)
+}
@@ -1,22 +1,21 @@
+use std::io;
 use std::path::Path;
 
-// TODO Handle all the unwraps here
-pub fn copy_static_files<D: AsRef<Path>>(files: D, dir: D) {
-    let files = files.as_ref();
-    let dir = dir.as_ref();
+use crate::file_system::FileSystem;
 
-    let files = files.read_dir().unwrap();
+// Copies the files of a static directory, including its subdirectories, into 'dir'.
+// The files are read from 'from' and written to 'to', which may be different file systems.
+pub fn copy_static_files(from: &dyn FileSystem, files: &Path, to: &dyn FileSystem, dir: &Path) -> io::Result<()> {
+    for f in from.read_dir(files)? {
+        let target = dir.join(f.path.file_name().unwrap());
 
-    for f in files {
-        let f = f.unwrap();
-
-        let ft = f.file_type().unwrap();
-
-        if ft.is_dir() {
-            std::fs::create_dir(dir.join(f.path())).unwrap();
-            copy_static_files(f.path(), dir.join(f.path()));
+        if f.is_dir {
+            to.create_dir_all(&target)?;
+            copy_static_files(from, &f.path, to, &target)?;
         } else {
-            std::fs::copy(f.path(), dir.join(f.file_name())).unwrap();
+            to.write(&target, &from.read(&f.path)?)?;
         }
     }
+
+    Ok(())
 }
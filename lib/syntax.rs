@@ -0,0 +1,343 @@
+use std::fmt;
+use std::fmt::Write;
+use std::ops::Range;
+
+use crate::content_tree;
+use crate::content_tree::Branch;
+use crate::content_tree::ContentTree;
+use crate::content_tree::Node;
+use crate::content_tree::SyntaxError;
+use crate::content_tree::Tag;
+use crate::json::Json;
+
+// A concrete syntax tree, which keeps every byte of the input as it was written,
+// e.g. for formatters and refactoring tools. Printing it gives back the input,
+// even if the input contains errors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTree<'a> {
+    pub children: Vec<SyntaxNode<'a>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxNode<'a> {
+    Tag(SyntaxTag<'a>),
+    Text(&'a str),
+    // A closing tag or branch outside of a tag, which is left out of the content tree.
+    Invalid(&'a str),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxTag<'a> {
+    // The bytes of the whole tag, including its content and closing tag
+    pub span: Range<usize>,
+    // The opening tag as written, e.g. '<< if page.draft <<' or '<<title>>'
+    pub open: &'a str,
+    pub name: &'a str,
+    pub args: &'a str,
+    // The content of a block, which a self-closing tag doesn't have.
+    pub content: Option<SyntaxTree<'a>>,
+    pub branches: Vec<SyntaxBranch<'a>>,
+    // The closing tag as written, e.g. '>>if>>',
+    // which is empty for self-closing tags and tags that are never closed.
+    pub close: &'a str,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxBranch<'a> {
+    // The branch as written, e.g. '>> else <<'
+    pub open: &'a str,
+    pub name: &'a str,
+    pub args: &'a str,
+    pub content: SyntaxTree<'a>,
+}
+
+impl<'a> SyntaxTree<'a> {
+    // Parses the whole input, even if it contains errors, and returns all of them.
+    pub fn parse(input: &'a str) -> (Self, Vec<SyntaxError>) {
+        content_tree::parse_lossless(input)
+    }
+
+    // The content tree, which only keeps what affects the output.
+    pub fn to_content_tree(&self) -> ContentTree<'a> {
+        let children = self.children.iter().filter_map(|node| match node {
+            SyntaxNode::Tag(tag) => Some(Node::Tag(Tag {
+                name: tag.name,
+                args: tag.args,
+                content: tag.content.as_ref().map_or_else(|| ContentTree { children: Vec::new() }, |c| c.to_content_tree()),
+                branches: tag.branches.iter().map(|b| Branch {
+                    name: b.name,
+                    args: b.args,
+                    content: b.content.to_content_tree(),
+                }).collect(),
+            })),
+            SyntaxNode::Text(text) => Some(Node::Text(text)),
+            SyntaxNode::Invalid(_) => None,
+        }).collect();
+
+        ContentTree {
+            children,
+        }
+    }
+
+    // The tree as a JSON array of its nodes, e.g.
+    // '[{"type":"text","span":{"start":0,"end":1},"text":"a"}]'.
+    // Tags have a 'name', 'args', 'self_closing', 'content' and 'branches',
+    // and closing tags and branches outside of tags are of the type 'invalid'.
+    // The spans are in bytes of the input, after 'start', e.g. where the input starts in its file.
+    pub fn to_json(&self, start: usize) -> String {
+        json_nodes(self, &mut start.clone()).to_string()
+    }
+
+    // The tree as an indented outline, with a node on each line, e.g.
+    //
+    // tag 'if' 0..31 args 'page.draft'
+    //   text 13..18 "Draft"
+    //   branch 'else' 18..26
+    //     text 26..31 "Final"
+    //
+    // The spans are in bytes of the input, after 'start', like in 'to_json'.
+    pub fn outline(&self, start: usize) -> String {
+        let mut out = String::new();
+
+        write_outline(&mut out, self, 0, &mut start.clone());
+
+        out
+    }
+}
+
+fn span(start: usize, end: usize) -> Json {
+    Json::object(vec![
+        ("start", Json::from(start)),
+        ("end", Json::from(end)),
+    ])
+}
+
+// The nodes of the tree as JSON, where 'offset' is where the tree starts, and afterwards ends.
+fn json_nodes(tree: &SyntaxTree, offset: &mut usize) -> Json {
+    let mut nodes = Vec::new();
+
+    for node in &tree.children {
+        let start = *offset;
+
+        match node {
+            SyntaxNode::Tag(tag) => {
+                *offset += tag.open.len();
+
+                let content = match &tag.content {
+                    Some(content) => json_nodes(content, offset),
+                    None => Json::Array(Vec::new()),
+                };
+
+                let mut branches = Vec::new();
+                for branch in &tag.branches {
+                    let start = *offset;
+                    *offset += branch.open.len();
+
+                    let content = json_nodes(&branch.content, offset);
+
+                    branches.push(Json::object(vec![
+                        ("name", Json::string(branch.name)),
+                        ("args", Json::string(branch.args)),
+                        ("span", span(start, *offset)),
+                        ("content", content),
+                    ]));
+                }
+
+                *offset += tag.close.len();
+
+                nodes.push(Json::object(vec![
+                    ("type", Json::string("tag")),
+                    ("name", Json::string(tag.name)),
+                    ("args", Json::string(tag.args)),
+                    ("span", span(start, *offset)),
+                    ("self_closing", Json::Bool(tag.content.is_none())),
+                    ("content", content),
+                    ("branches", Json::Array(branches)),
+                ]));
+            },
+            SyntaxNode::Text(text) | SyntaxNode::Invalid(text) => {
+                *offset += text.len();
+
+                let t = match node {
+                    SyntaxNode::Text(_) => "text",
+                    _ => "invalid",
+                };
+
+                nodes.push(Json::object(vec![
+                    ("type", Json::string(t)),
+                    ("span", span(start, *offset)),
+                    ("text", Json::string(*text)),
+                ]));
+            },
+        }
+    }
+
+    Json::Array(nodes)
+}
+
+fn write_outline(out: &mut String, tree: &SyntaxTree, depth: usize, offset: &mut usize) {
+    let indent = "  ".repeat(depth);
+
+    for node in &tree.children {
+        let start = *offset;
+
+        match node {
+            SyntaxNode::Tag(tag) => {
+                write!(out, "{}tag '{}' {}..{}", indent, tag.name, start, start + tag.span.len()).unwrap();
+
+                if !tag.args.is_empty() {
+                    write!(out, " args '{}'", tag.args).unwrap();
+                }
+
+                if tag.content.is_none() {
+                    write!(out, " self-closing").unwrap();
+                }
+
+                writeln!(out).unwrap();
+
+                *offset += tag.open.len();
+
+                if let Some(content) = &tag.content {
+                    write_outline(out, content, depth + 1, offset);
+                }
+
+                for branch in &tag.branches {
+                    let end = *offset + branch.open.len() + branch.content.to_string().len();
+
+                    write!(out, "{}  branch '{}' {}..{}", indent, branch.name, *offset, end).unwrap();
+
+                    if !branch.args.is_empty() {
+                        write!(out, " args '{}'", branch.args).unwrap();
+                    }
+
+                    writeln!(out).unwrap();
+
+                    *offset += branch.open.len();
+
+                    write_outline(out, &branch.content, depth + 2, offset);
+                }
+
+                *offset += tag.close.len();
+            },
+            SyntaxNode::Text(text) => {
+                *offset += text.len();
+                writeln!(out, "{}text {}..{} {:?}", indent, start, *offset, text).unwrap();
+            },
+            SyntaxNode::Invalid(text) => {
+                *offset += text.len();
+                writeln!(out, "{}invalid {}..{} {:?}", indent, start, *offset, text).unwrap();
+            },
+        }
+    }
+}
+
+impl fmt::Display for SyntaxTree<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for child in &self.children {
+            match child {
+                SyntaxNode::Tag(tag) => {
+                    write!(f, "{}", tag.open)?;
+
+                    if let Some(content) = &tag.content {
+                        write!(f, "{}", content)?;
+                    }
+
+                    for branch in &tag.branches {
+                        write!(f, "{}{}", branch.open, branch.content)?;
+                    }
+
+                    write!(f, "{}", tag.close)?;
+                },
+                SyntaxNode::Text(text) | SyntaxNode::Invalid(text) => write!(f, "{}", text)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::content_tree::parse_lossless;
+
+    #[test]
+    fn prints_input_unchanged() {
+        let inputs = [
+            "",
+            "abc",
+            "<< foo >>",
+            "<<baz<<>>baz>>",
+            "<<baz>>",
+            "<< for p in posts <<<<p.title>>>> else <<None>>for >>",
+            "åäö <<if a<<\r\nx\r\n>>if>>",
+            // Errors
+            "a>>x>>b>> else <<c",
+            "<<a<<<<b<<x>>a>>y",
+            "<<foo<<bar>>baz>>",
+            "<<foo<<bar",
+            "a << b",
+        ];
+
+        for input in &inputs {
+            let (tree, _) = parse_lossless(input);
+
+            assert_eq!(&tree.to_string(), input);
+        }
+    }
+
+    #[test]
+    fn keeps_spelling_of_tags() {
+        let (tree, errors) = parse_lossless("x<< foo  bar <<y>>  foo>><<baz<<>>baz>>");
+
+        assert!(errors.is_empty());
+
+        match &tree.children[1] {
+            super::SyntaxNode::Tag(tag) => {
+                assert_eq!(tag.span, 1..25);
+                assert_eq!(tag.open, "<< foo  bar <<");
+                assert_eq!((tag.name, tag.args), ("foo", "bar"));
+                assert_eq!(tag.close, ">>  foo>>");
+            },
+            _ => panic!("Expected a tag"),
+        }
+
+        match &tree.children[2] {
+            super::SyntaxNode::Tag(tag) => assert!(tag.content.as_ref().unwrap().children.is_empty()),
+            _ => panic!("Expected a tag"),
+        }
+    }
+
+    #[test]
+    fn content_tree_drops_spelling() {
+        let (tree, _) = parse_lossless("<< foo >><<bar<<>>bar>>>>x>>");
+
+        assert_eq!(tree.to_content_tree().to_string(), "<<foo>><<bar>>");
+    }
+
+    #[test]
+    fn outline_with_spans() {
+        let (tree, _) = parse_lossless("<<if page.draft<<Draft>>else<<Final>>if>>\n>>x>><<a>>");
+
+        assert_eq!(tree.outline(10), "\
+tag 'if' 10..51 args 'page.draft'
+  text 27..32 \"Draft\"
+  branch 'else' 32..45
+    text 40..45 \"Final\"
+text 51..52 \"\\n\"
+invalid 52..57 \">>x>>\"
+tag 'a' 57..62 self-closing
+");
+    }
+
+    #[test]
+    fn json_with_spans() {
+        let (tree, _) = parse_lossless("a<<b c<<d>>else<<>>b>>");
+
+        assert_eq!(tree.to_json(0), concat!(
+            r#"[{"span":{"end":1,"start":0},"text":"a","type":"text"},"#,
+            r#"{"args":"c","branches":[{"args":"","content":[],"name":"else","span":{"end":17,"start":9}}],"#,
+            r#""content":[{"span":{"end":9,"start":8},"text":"d","type":"text"}],"#,
+            r#""name":"b","self_closing":false,"span":{"end":22,"start":1},"type":"tag"}]"#,
+        ));
+    }
+}
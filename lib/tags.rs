@@ -0,0 +1,33 @@
+use std::collections::HashMap;
+
+use crate::content_tree::Tag;
+use crate::context::Context;
+
+pub type TagHandler = Box<dyn Fn(&Tag, &Context) -> Result<String, String>>;
+
+// Tags implemented in Rust, e.g. '<<now>>' or '<<include-csv file="x.csv">>'.
+// A handler receives the tag as it is written, with its arguments and unrendered content,
+// and the variables where it is used. Its output is used as it is.
+#[derive(Default)]
+pub struct Tags {
+    tags: HashMap<String, TagHandler>,
+}
+
+impl Tags {
+    pub fn new() -> Tags {
+        Tags::default()
+    }
+
+    pub fn insert<F>(&mut self, name: &str, handler: F) where
+        F: Fn(&Tag, &Context) -> Result<String, String> + 'static {
+        self.tags.insert(name.to_owned(), Box::new(handler));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&TagHandler> {
+        self.tags.get(name)
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.tags.contains_key(name)
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use crate::context::Value;
+use crate::filters::slugify;
+use crate::pages::Page;
+
+// A taxonomy groups pages by the values of a front matter list, e.g. 'tags: [rust, web]'.
+#[derive(Debug)]
+pub struct Taxonomy {
+    pub name: String,
+    // Sorted by slug
+    pub terms: Vec<Term>,
+}
+
+#[derive(Debug)]
+pub struct Term {
+    pub name: String,
+    pub slug: String,
+    // Indices of the pages with this term
+    pub pages: Vec<usize>,
+}
+
+impl Taxonomy {
+    pub fn collect(name: &str, pages: &[Page]) -> Taxonomy {
+        let mut terms: BTreeMap<String, Term> = BTreeMap::new();
+
+        for (i, page) in pages.iter().enumerate() {
+            for term in page_terms(name, page) {
+                let slug = slugify(&term);
+
+                let t = terms.entry(slug.clone()).or_insert_with(|| Term {
+                    name: term,
+                    slug,
+                    pages: Vec::new(),
+                });
+
+                // Terms of a page may differ only in case, e.g. 'Rust' and 'rust'.
+                if t.pages.last() != Some(&i) {
+                    t.pages.push(i);
+                }
+            }
+        }
+
+        Taxonomy {
+            name: name.to_owned(),
+            terms: terms.into_values().collect(),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("/{}/", self.name)
+    }
+
+    // The output path of the page listing all terms.
+    pub fn path(&self) -> PathBuf {
+        PathBuf::from(&self.name).join("index.html")
+    }
+
+    pub fn term_url(&self, term: &Term) -> String {
+        format!("/{}/{}/", self.name, term.slug)
+    }
+
+    // The output path of the page listing all pages with the term.
+    pub fn term_path(&self, term: &Term) -> PathBuf {
+        PathBuf::from(&self.name).join(&term.slug).join("index.html")
+    }
+
+    // The 'taxonomy' variable: its name, url and terms, each with its pages.
+    pub fn to_value(&self, pages: &[Page]) -> Value {
+        let terms = self.terms.iter().map(|t| self.term_value(t, pages)).collect();
+
+        let mut m = BTreeMap::new();
+        m.insert("name".to_owned(), Value::String(self.name.clone()));
+        m.insert("url".to_owned(), Value::String(self.url()));
+        m.insert("terms".to_owned(), Value::List(terms));
+
+        Value::Map(m)
+    }
+
+    // The 'term' variable: its name, slug, url and pages.
+    pub fn term_value(&self, term: &Term, pages: &[Page]) -> Value {
+        let mut m = self.term_link(&term.name);
+
+        let p = term.pages.iter().map(|&i| pages[i].to_value()).collect();
+        m.insert("pages".to_owned(), Value::List(p));
+
+        Value::Map(m)
+    }
+
+    // The terms of a page, without their pages, to link to them from the page.
+    pub fn page_value(&self, page: &Page) -> Value {
+        let mut slugs = Vec::new();
+
+        let terms = page_terms(&self.name, page)
+            .iter()
+            .filter(|t| {
+                let slug = slugify(t);
+                let new = !slugs.contains(&slug);
+                slugs.push(slug);
+                new
+            })
+            .map(|t| Value::Map(self.term_link(t)))
+            .collect();
+
+        Value::List(terms)
+    }
+
+    fn term_link(&self, name: &str) -> BTreeMap<String, Value> {
+        let slug = slugify(name);
+
+        let mut m = BTreeMap::new();
+        m.insert("name".to_owned(), Value::String(name.to_owned()));
+        m.insert("url".to_owned(), Value::String(format!("/{}/{}/", self.name, slug)));
+        m.insert("slug".to_owned(), Value::String(slug));
+
+        m
+    }
+}
+
+// A single value is treated as a list with one term.
+fn page_terms(taxonomy: &str, page: &Page) -> Vec<String> {
+    match page.front_matter.get(taxonomy) {
+        Some(Value::List(l)) => l.iter().map(|v| v.to_string()).filter(|t| !slugify(t).is_empty()).collect(),
+        Some(Value::String(s)) if !slugify(s).is_empty() => vec![s.clone()],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::diagnostic::Origin;
+
+    fn page(path: &str, tags: &[&str]) -> Page {
+        let mut front_matter = BTreeMap::new();
+        let tags = tags.iter().map(|t| Value::String(t.to_string())).collect();
+        front_matter.insert("tags".to_owned(), Value::List(tags));
+
+        Page {
+            file: PathBuf::from(path),
+            path: PathBuf::from(path),
+            front_matter,
+            variables: BTreeMap::new(),
+            body: String::new(),
+            body_start: Origin::START,
+            modified: None,
+        }
+    }
+
+    #[test]
+    fn collects_terms_by_slug() {
+        let pages = vec![
+            page("a.html", &["Rust", "Web Dev"]),
+            page("b.html", &["rust"]),
+            page("c.html", &[]),
+            page("d.html", &["Rust", "rust"]),
+        ];
+
+        let t = Taxonomy::collect("tags", &pages);
+
+        assert_eq!(t.terms.len(), 2);
+        assert_eq!(t.terms[0].name, "Rust");
+        assert_eq!(t.terms[0].pages, vec![0, 1, 3]);
+
+        match t.page_value(&pages[3]) {
+            Value::List(l) => assert_eq!(l.len(), 1),
+            _ => panic!("Expected a list"),
+        }
+
+        assert_eq!(t.terms[1].slug, "web-dev");
+        assert_eq!(t.term_url(&t.terms[1]), "/tags/web-dev/");
+        assert_eq!(t.term_path(&t.terms[1]), PathBuf::from("tags/web-dev/index.html"));
+    }
+
+    #[test]
+    fn terms_of_page() {
+        let pages = vec![page("a.html", &["Web Dev"])];
+
+        let t = Taxonomy::collect("tags", &pages);
+
+        let v = t.page_value(&pages[0]);
+
+        match v {
+            Value::List(l) => {
+                assert_eq!(l[0].get("url"), Some(&Value::String("/tags/web-dev/".to_owned())));
+                assert_eq!(l[0].get("name"), Some(&Value::String("Web Dev".to_owned())));
+            },
+            _ => panic!("Expected a list"),
+        }
+    }
+}
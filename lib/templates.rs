@@ -1,44 +1,30 @@
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::fs::File;
-use std::fs::create_dir;
-use std::fs::read_to_string;
-use std::io::Write;
+use std::io;
 use std::path::Path;
 
 use crate::content_tree::ContentTree;
 use crate::content_tree::Node;
+use crate::content_tree::OwnedContentTree;
 use crate::content_tree::Tag;
+use crate::file_system::FileSystem;
+use crate::source;
 
 // Takes all templates and makes all extended templates stand-alone.
-pub fn flatten_templates<D: AsRef<Path>, T: AsRef<Path>>(templates: &Vec<D>, tmp: &T) {
-    // The 'file_content' is only used to make sure the Strings is owned by something.
-    let mut file_content: Vec<String> = Vec::new(); 
-    let mut content: Vec<ContentTree> = Vec::new();
-    let mut filenames: Vec<String> = Vec::new();
+// Returns the templates by name. The templates must have been checked for syntax errors.
+pub fn flatten_templates<D: AsRef<Path>>(fs: &dyn FileSystem, templates: &[D]) -> io::Result<HashMap<String, OwnedContentTree>> {
+    let mut owned: Vec<(String, OwnedContentTree)> = Vec::new();
 
     for dir in templates {
-        let files = dir.as_ref().read_dir().unwrap();
-
-        for f in files {
-            let f = f.unwrap();
-
-            let ft = f.file_type().unwrap();
-
-            if ft.is_file() {
-                filenames.push(f.path().file_stem().unwrap().to_owned().into_string().unwrap());
-                let fc = read_to_string(f.path()).unwrap();
-                file_content.push(fc);
-            }
+        for f in source::read_dir(fs, dir)? {
+            owned.push((f.name, ContentTree::try_from(f.content.as_ref()).unwrap().into_owned()));
         }
     }
 
-    for fc in &file_content {
-        let c = ContentTree::try_from(fc.as_ref()).unwrap();
-        content.push(c);
-    }
+    let content: Vec<ContentTree> = owned.iter().map(|(_, c)| c.as_tree()).collect();
+    let filenames: Vec<&str> = owned.iter().map(|(n, _)| n.as_ref()).collect();
 
-    let edges = create_edges(filenames.iter().map(|s| s.as_ref()).zip(&content).collect());
+    let edges = create_edges(filenames.iter().copied().zip(&content).collect());
 
     let hiers = build_hierarchy(edges);
 
@@ -61,7 +47,7 @@ pub fn flatten_templates<D: AsRef<Path>, T: AsRef<Path>>(templates: &Vec<D>, tmp
                 .unwrap();
 
             // "Remove" the outermost tag, which is just the name of the parent
-            debug_assert!(matches!(&c.children[0], Node::Tag(t) if &t.name == &hier.name));
+            debug_assert!(matches!(&c.children[0], Node::Tag(t) if t.name == hier.name));
             let c = match &c.children[0] {
                 Node::Tag(t) => &t.content,
                 _ => panic!(),
@@ -74,7 +60,7 @@ pub fn flatten_templates<D: AsRef<Path>, T: AsRef<Path>>(templates: &Vec<D>, tmp
         }
     }
 
-    let fnc: Vec<(&str, &ContentTree)> = filenames.iter().map(|s| s.as_ref()).zip(content.iter()).collect();
+    let fnc: Vec<(&str, &ContentTree)> = filenames.iter().copied().zip(content.iter()).collect();
     for hier in &hiers {
         let &(n, c) = 
             fnc.iter()
@@ -84,41 +70,25 @@ pub fn flatten_templates<D: AsRef<Path>, T: AsRef<Path>>(templates: &Vec<D>, tmp
         // All root templates are already flattened.
         flattened.push((n, c.clone()));
 
-        recursive(&mut flattened, &fnc, &hier);
+        recursive(&mut flattened, &fnc, hier);
     }
 
-    let templ = tmp.as_ref().join("templates/");
-    create_dir(&templ).unwrap();
-
-    for (name, content) in flattened {
-        let mut file = File::create(templ.join(name)).unwrap();
-        write!(&mut file, "{}", content).unwrap();
-    }
+    Ok(flattened.into_iter()
+        .map(|(name, content)| (name.to_owned(), content.into_owned()))
+        .collect())
 }
 
-fn flatten<'a>(parent: &ContentTree<'a>, child: &ContentTree<'a>) -> ContentTree<'a> {
+pub fn flatten<'a>(parent: &ContentTree<'a>, child: &ContentTree<'a>) -> ContentTree<'a> {
     let mut content = parent.clone();
 
-    let mut tlt: HashMap<&str, &mut Tag> = HashMap::new();
-    for node in &mut content.children {
-        match node {
-            Node::Tag(tag) => tlt.insert(tag.name, tag),
-            Node::Text(_) => continue,
-        };
-    }
+    let mut tlt: HashMap<&str, &mut Tag> = content.tags_mut().map(|tag| (tag.name, tag)).collect();
 
-    for node in &child.children {
-        match node {
-            Node::Tag(tag) => {
-                if tlt.contains_key(tag.name) {
-                    let t = tlt.get_mut(tag.name).unwrap();
-                    t.content = tag.content.clone();
-                }
-            },
-            Node::Text(_) => continue,
+    for tag in child.tags() {
+        if let Some(t) = tlt.get_mut(tag.name) {
+            t.content = tag.content.clone();
         }
     }
-    
+
     content
 }
 
@@ -128,34 +98,70 @@ fn create_edges<'a, 'b>(
     let mut v = Vec::new();
 
     for (filename, c) in &content {
-        // Many editors insert a newline at the end of a file,
-        // which will create a Text node at the end of the Content tree.
-        let has_trailing_whitespace: bool = if c.children.len() == 2 {
-            let child = &c.children[1];
-            if let Node::Text(text) = child {
-                if text.chars().all(|c| c.is_ascii_whitespace()) {
-                    true
-                } else {
-                    false
-                }
-            } else {
-                false
+        // A template consisting of a single tag, e.g. a 'for', isn't necessarily extending another.
+        let parent = parent_tag(c)
+            .map(|tag| tag.name)
+            .filter(|name| content.iter().any(|(f, _)| f == name));
+
+        v.push((parent, *filename));
+    }
+
+    v
+}
+
+// Returns the templates which (indirectly) extend themselves.
+// These have no root, so they are left out when flattening.
+pub fn extending_themselves<'a, 'b>(content: Vec<(&'b str, &ContentTree<'a>)>) -> Vec<&'b str> {
+    let edges = create_edges(content);
+
+    let parent_of = |name: &str| edges.iter()
+        .find(|(_, child)| *child == name)
+        .and_then(|(parent, _)| *parent);
+
+    let mut v = Vec::new();
+
+    for (_, start) in &edges {
+        let mut current: &str = start;
+
+        // A chain longer than the number of templates must contain a cycle.
+        for _ in 0..edges.len() {
+            match parent_of(current) {
+                Some(parent) if parent == *start => {
+                    v.push(*start);
+                    break;
+                },
+                Some(parent) => current = parent,
+                None => break,
             }
+        }
+    }
+
+    v
+}
+
+// Returns the tag naming the parent, if the content extends another template,
+// i.e. if the content consists of nothing but a single tag.
+pub fn parent_tag<'c, 'a>(c: &'c ContentTree<'a>) -> Option<&'c Tag<'a>> {
+    // Many editors insert a newline at the end of a file,
+    // which will create a Text node at the end of the Content tree.
+    let has_trailing_whitespace: bool = if c.children.len() == 2 {
+        let child = &c.children[1];
+        if let Node::Text(text) = child {
+            text.chars().all(|c| c.is_ascii_whitespace())
         } else {
             false
-        };
-
-        if c.children.len() == 1 || has_trailing_whitespace {
-            if let Node::Tag(tag) = &c.children[0] {
-                v.push((Some(tag.name), *filename));
-                continue;
-            }
         }
+    } else {
+        false
+    };
 
-        v.push((None, *filename));
+    if c.children.len() == 1 || has_trailing_whitespace {
+        if let Node::Tag(tag) = &c.children[0] {
+            return Some(tag);
+        }
     }
 
-    v
+    None
 }
 
 #[derive(Debug)]
@@ -179,11 +185,10 @@ fn build_hierarchy<'a>(edges: Vec<(Option<&'a str>, &'a str)>) -> Vec<Hierarchy<
                     children: Vec::new(),
                 });
             } else {
-                let mut v = Vec::new();
-                v.push(Hierarchy {
+                let v = vec![Hierarchy {
                     name: child,
                     children: Vec::new(),
-                });
+                }];
 
                 nodes.insert(parent, v);
             }
@@ -204,8 +209,8 @@ fn build_hierarchy<'a>(edges: Vec<(Option<&'a str>, &'a str)>) -> Vec<Hierarchy<
             None => return,
         };
 
-        while !v.is_empty() {
-            h.children.push(v.pop().unwrap());
+        while let Some(child) = v.pop() {
+            h.children.push(child);
             add_children(nodes, h.children.last_mut().unwrap());
         }
     }
@@ -277,6 +282,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::useless_vec)]
     fn ignores_trailing_newline() {
         let t_root = "This is the root template.\n";
         let t_child = "<<root<<This template extends root>>root>>\n";
@@ -288,10 +294,33 @@ mod tests {
         assert_eq!(edges, vec![(None, "root"), (Some("root"), "child")]);
     }
 
+    #[test]
+    fn single_tag_is_not_a_parent_unless_it_is_a_template() {
+        let t_list = "<<for p in posts<<<<p.title>>>>for>>\n";
+
+        let pairs = [("list", ContentTree::try_from(t_list).unwrap())];
+
+        let edges = create_edges(pairs.iter().map(|(a, b)| (*a, b)).collect());
+
+        assert_eq!(edges, vec![(None, "list")]);
+    }
+
+    #[test]
+    fn templates_extending_themselves() {
+        let pairs = [
+            ("a", ContentTree::try_from("<<b<<x>>b>>").unwrap()),
+            ("b", ContentTree::try_from("<<a<<y>>a>>").unwrap()),
+            ("c", ContentTree::try_from("<<a<<z>>a>>").unwrap()),
+        ];
+
+        let v = extending_themselves(pairs.iter().map(|(a, b)| (*a, b)).collect());
+
+        assert_eq!(v, vec!["a", "b"]);
+    }
+
     #[test]
     fn simple_template_flattening() {
-        use std::fs::read_to_string;
-        use tempfile::tempdir;
+        use crate::file_system::MemoryFs;
 
         let t_base = "\
 <!DOCTYPE html>
@@ -321,17 +350,11 @@ mod tests {
 >>base>>
 ";
 
-        let out = tempdir().unwrap();
-
-        let mut file = File::create(out.path().join("base.html")).unwrap();
-        write!(file, "{}", t_base).unwrap();
-
-        let mut file = File::create(out.path().join("post.html")).unwrap();
-        write!(file, "{}", t_post).unwrap();
+        let fs = MemoryFs::new();
+        fs.insert("templates/base.html", t_base);
+        fs.insert("templates/post.html", t_post);
 
-        let dirs = vec![out];
-        let tmp = tempdir().unwrap();
-        flatten_templates(&dirs, &tmp);
+        let flattened = flatten_templates(&fs, &["templates"]).unwrap();
 
         let f_base = t_base;
 
@@ -357,8 +380,7 @@ mod tests {
 </html>
 ";
 
-        let templ = tmp.path().join("templates/");
-        assert_eq!(read_to_string(templ.join("base")).unwrap(), f_base);
-        assert_eq!(read_to_string(templ.join("post")).unwrap(), f_post);
+        assert_eq!(flattened["base"].to_string(), f_base);
+        assert_eq!(flattened["post"].to_string(), f_post);
     }
 }
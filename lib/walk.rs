@@ -0,0 +1,340 @@
+use std::mem;
+
+use crate::content_tree::ContentTree;
+use crate::content_tree::Node;
+use crate::content_tree::Tag;
+
+// Where a node is in a content tree, e.g. to change it after finding it with 'walk'.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct NodePath {
+    // From the top of the tree to the node
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Step {
+    // Which content of the tag of the previous step the node is in:
+    // None for the content of the tag itself, or the index of one of its branches.
+    // None for the first step.
+    pub branch: Option<usize>,
+    // The index of the node among the children of that content
+    pub index: usize,
+}
+
+impl NodePath {
+    // The path of a node at the top of a tree.
+    pub fn new(index: usize) -> NodePath {
+        NodePath {
+            steps: vec![Step {
+                branch: None,
+                index,
+            }],
+        }
+    }
+
+    // The path of a node in the content of this tag, or in one of its branches.
+    pub fn child(&self, branch: Option<usize>, index: usize) -> NodePath {
+        let mut path = self.clone();
+        path.steps.push(Step {
+            branch,
+            index,
+        });
+        path
+    }
+
+    // The path of the tag this node is in, if it isn't at the top.
+    pub fn parent(&self) -> Option<NodePath> {
+        match self.steps.len() {
+            0 | 1 => None,
+            n => Some(NodePath {
+                steps: self.steps[..n - 1].to_vec(),
+            }),
+        }
+    }
+}
+
+// Visits the nodes of a tree depth first, and may change them on the way.
+// A tag is visited before its content and branches, so changes to them are visited as well.
+pub trait VisitMut<'a> {
+    fn visit_tag(&mut self, _tag: &mut Tag<'a>) {}
+
+    fn visit_text(&mut self, _text: &mut &'a str) {}
+}
+
+// Iterates over the nodes of a tree depth first, see 'ContentTree::walk'.
+pub struct Walk<'t, 'a> {
+    // The contents being walked, the innermost last: the content, the path of its tag,
+    // which content of the tag it is, and the index of its next node.
+    stack: Vec<(&'t ContentTree<'a>, Vec<Step>, Option<usize>, usize)>,
+}
+
+impl<'t, 'a> Iterator for Walk<'t, 'a> {
+    type Item = (NodePath, &'t Node<'a>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (tree, steps, branch, next) = self.stack.last_mut()?;
+
+            let node = match tree.children.get(*next) {
+                Some(node) => node,
+                None => {
+                    self.stack.pop();
+                    continue;
+                },
+            };
+
+            let mut path = steps.clone();
+            path.push(Step {
+                branch: *branch,
+                index: *next,
+            });
+
+            *next += 1;
+
+            if let Node::Tag(tag) = node {
+                // The content is walked before the branches.
+                for (i, b) in tag.branches.iter().enumerate().rev() {
+                    self.stack.push((&b.content, path.clone(), Some(i), 0));
+                }
+                self.stack.push((&tag.content, path.clone(), None, 0));
+            }
+
+            return Some((NodePath { steps: path }, node));
+        }
+    }
+}
+
+impl<'a> ContentTree<'a> {
+    // Iterates over all nodes of the tree depth first, with their paths.
+    // A tag comes before its content, which comes before its branches.
+    pub fn walk(&self) -> Walk<'_, 'a> {
+        Walk {
+            stack: vec![(self, Vec::new(), None, 0)],
+        }
+    }
+
+    // The tags at the top of the tree, e.g. the blocks of a template.
+    pub fn tags(&self) -> impl Iterator<Item = &Tag<'a>> {
+        self.children.iter().filter_map(|node| match node {
+            Node::Tag(tag) => Some(tag),
+            Node::Text(_) => None,
+        })
+    }
+
+    pub fn tags_mut(&mut self) -> impl Iterator<Item = &mut Tag<'a>> {
+        self.children.iter_mut().filter_map(|node| match node {
+            Node::Tag(tag) => Some(tag),
+            Node::Text(_) => None,
+        })
+    }
+
+    // Finds the tags with the name anywhere in the tree, depth first.
+    pub fn find_tags<'t>(&'t self, name: &'t str) -> impl Iterator<Item = (NodePath, &'t Tag<'a>)> {
+        self.walk().filter_map(move |(path, node)| match node {
+            Node::Tag(tag) if tag.name == name => Some((path, tag)),
+            _ => None,
+        })
+    }
+
+    pub fn get(&self, path: &NodePath) -> Option<&Node<'a>> {
+        let step = path.steps.last()?;
+
+        self.content_of(path)?.children.get(step.index)
+    }
+
+    pub fn get_mut(&mut self, path: &NodePath) -> Option<&mut Node<'a>> {
+        let step = *path.steps.last()?;
+
+        self.content_of_mut(path)?.children.get_mut(step.index)
+    }
+
+    // Replaces the node at the path, and returns the node which was there.
+    // Returns None, and leaves the tree as it is, if there is no node at the path.
+    pub fn replace(&mut self, path: &NodePath, node: Node<'a>) -> Option<Node<'a>> {
+        self.get_mut(path).map(|n| mem::replace(n, node))
+    }
+
+    // Inserts the node at the path, before the node which is there, if any.
+    // Returns whether it was inserted, which it isn't if the path leads nowhere.
+    pub fn insert(&mut self, path: &NodePath, node: Node<'a>) -> bool {
+        let step = match path.steps.last() {
+            Some(step) => *step,
+            None => return false,
+        };
+
+        match self.content_of_mut(path) {
+            Some(content) if step.index <= content.children.len() => {
+                content.children.insert(step.index, node);
+                true
+            },
+            _ => false,
+        }
+    }
+
+    // Removes the node at the path and returns it.
+    pub fn remove(&mut self, path: &NodePath) -> Option<Node<'a>> {
+        let step = *path.steps.last()?;
+
+        let content = self.content_of_mut(path)?;
+
+        if step.index < content.children.len() {
+            Some(content.children.remove(step.index))
+        } else {
+            None
+        }
+    }
+
+    // Visits every node of the tree depth first, see 'VisitMut'.
+    pub fn visit_mut<V: VisitMut<'a>>(&mut self, visitor: &mut V) {
+        for node in &mut self.children {
+            match node {
+                Node::Tag(tag) => {
+                    visitor.visit_tag(tag);
+
+                    tag.content.visit_mut(visitor);
+
+                    for branch in &mut tag.branches {
+                        branch.content.visit_mut(visitor);
+                    }
+                },
+                Node::Text(text) => visitor.visit_text(text),
+            }
+        }
+    }
+
+    // The content which the last step of the path is in.
+    fn content_of(&self, path: &NodePath) -> Option<&ContentTree<'a>> {
+        if path.steps.first()?.branch.is_some() {
+            return None;
+        }
+
+        let mut content = self;
+
+        for steps in path.steps.windows(2) {
+            let tag = match content.children.get(steps[0].index)? {
+                Node::Tag(tag) => tag,
+                Node::Text(_) => return None,
+            };
+
+            content = match steps[1].branch {
+                None => &tag.content,
+                Some(b) => &tag.branches.get(b)?.content,
+            };
+        }
+
+        Some(content)
+    }
+
+    fn content_of_mut(&mut self, path: &NodePath) -> Option<&mut ContentTree<'a>> {
+        if path.steps.first()?.branch.is_some() {
+            return None;
+        }
+
+        let mut content = self;
+
+        for steps in path.steps.windows(2) {
+            let tag = match content.children.get_mut(steps[0].index)? {
+                Node::Tag(tag) => tag,
+                Node::Text(_) => return None,
+            };
+
+            content = match steps[1].branch {
+                None => &mut tag.content,
+                Some(b) => &mut tag.branches.get_mut(b)?.content,
+            };
+        }
+
+        Some(content)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+
+    const INPUT: &str = "a<<if b<<<<c>>>>else<<<<d>>e>>if>><<c>>";
+
+    #[test]
+    fn walks_depth_first() {
+        let tree = ContentTree::try_from(INPUT).unwrap();
+
+        let walked: Vec<(NodePath, String)> = tree.walk().map(|(path, node)| match node {
+            Node::Tag(tag) => (path, format!("<<{}>>", tag.name)),
+            Node::Text(text) => (path, text.to_string()),
+        }).collect();
+
+        let tag = NodePath::new(1);
+
+        assert_eq!(walked, vec![
+            (NodePath::new(0), "a".to_owned()),
+            (tag.clone(), "<<if>>".to_owned()),
+            (tag.child(None, 0), "<<c>>".to_owned()),
+            (tag.child(Some(0), 0), "<<d>>".to_owned()),
+            (tag.child(Some(0), 1), "e".to_owned()),
+            (NodePath::new(2), "<<c>>".to_owned()),
+        ]);
+
+        for (path, node) in tree.walk() {
+            assert_eq!(tree.get(&path), Some(node));
+        }
+    }
+
+    #[test]
+    fn finds_tags_by_name() {
+        let tree = ContentTree::try_from(INPUT).unwrap();
+
+        let paths: Vec<NodePath> = tree.find_tags("c").map(|(path, _)| path).collect();
+
+        assert_eq!(paths, vec![NodePath::new(1).child(None, 0), NodePath::new(2)]);
+        assert_eq!(paths[0].parent(), Some(NodePath::new(1)));
+        assert_eq!(tree.find_tags("x").count(), 0);
+    }
+
+    #[test]
+    fn edits_nodes() {
+        let mut tree = ContentTree::try_from(INPUT).unwrap();
+
+        let else_branch = NodePath::new(1).child(Some(0), 0);
+
+        let old = tree.replace(&else_branch, Node::Text("x"));
+        assert!(matches!(old, Some(Node::Tag(t)) if t.name == "d"));
+        assert!(tree.insert(&NodePath::new(1).child(None, 1), Node::Text("y")));
+        assert_eq!(tree.remove(&NodePath::new(0)), Some(Node::Text("a")));
+
+        assert_eq!(tree.to_string(), "<<if b<<<<c>>y>>else<<xe>>if>><<c>>");
+
+        // Paths leading nowhere
+        assert!(!tree.insert(&NodePath::new(5), Node::Text("z")));
+        assert_eq!(tree.remove(&NodePath::new(2)), None);
+        assert_eq!(tree.replace(&NodePath::new(1).child(Some(3), 0), Node::Text("z")), None);
+        assert_eq!(tree.get(&NodePath::new(0).child(None, 0).child(None, 0)), None);
+    }
+
+    #[test]
+    fn visits_and_changes_nodes() {
+        struct Rename;
+
+        impl<'a> VisitMut<'a> for Rename {
+            fn visit_tag(&mut self, tag: &mut Tag<'a>) {
+                if tag.name == "c" {
+                    tag.name = "f";
+                }
+
+                // Remove text from blocks.
+                tag.content.children.retain(|n| matches!(n, Node::Tag(_)));
+            }
+
+            fn visit_text(&mut self, text: &mut &'a str) {
+                *text = "_";
+            }
+        }
+
+        let mut tree = ContentTree::try_from(INPUT).unwrap();
+
+        tree.visit_mut(&mut Rename);
+
+        assert_eq!(tree.to_string(), "_<<if b<<<<f>>>>else<<<<d>>_>>if>><<f>>");
+    }
+}
@@ -0,0 +1,25 @@
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use super::MessageFormat;
+
+pub fn exec(args: &ArgMatches) {
+    let format = MessageFormat::from_args(args);
+
+    let m = match super::project(args) {
+        Ok(m) => m,
+        Err(e) => {
+            super::report(&e.into_diagnostics(), format);
+            exit(1);
+        },
+    };
+
+    match m.build() {
+        Ok(warnings) => super::report(&warnings, format),
+        Err(e) => {
+            super::report(&e.into_diagnostics(), format);
+            exit(1);
+        },
+    }
+}
@@ -0,0 +1,31 @@
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use super::MessageFormat;
+
+pub fn exec(args: &ArgMatches) {
+    let format = MessageFormat::from_args(args);
+
+    let diagnostics = match super::project(args).and_then(|m| m.check()) {
+        Ok(d) => d,
+        Err(e) => e.into_diagnostics(),
+    };
+
+    super::report(&diagnostics, format);
+
+    let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+    let warnings = diagnostics.len() - errors;
+
+    if format == MessageFormat::Human {
+        if errors > 0 {
+            eprintln!("{} error(s) and {} warning(s)", errors, warnings);
+        } else if warnings > 0 {
+            eprintln!("{} warning(s)", warnings);
+        }
+    }
+
+    if errors > 0 {
+        exit(1);
+    }
+}
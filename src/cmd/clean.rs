@@ -0,0 +1,16 @@
+use crate::dir;
+
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use mold::Mold;
+
+pub fn exec(_args: &ArgMatches) {
+    let m = Mold::new(dir::OUTPUT);
+
+    if let Err(e) = m.clean(".") {
+        eprintln!("error: {}", e);
+        exit(1);
+    }
+}
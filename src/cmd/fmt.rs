@@ -0,0 +1,28 @@
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use super::MessageFormat;
+
+pub fn exec(args: &ArgMatches) {
+    let check = args.is_present("check");
+
+    let result = super::project(args).and_then(|m| m.format(!check));
+
+    let files = match result {
+        Ok(files) => files,
+        Err(e) => {
+            super::report(&e.into_diagnostics(), MessageFormat::Human);
+            exit(1);
+        },
+    };
+
+    // List the files which aren't formatted, so that they can be fixed.
+    if check && !files.is_empty() {
+        for f in &files {
+            println!("{}", f.display());
+        }
+
+        exit(1);
+    }
+}
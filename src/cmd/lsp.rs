@@ -0,0 +1,15 @@
+use std::io;
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use super::MessageFormat;
+
+pub fn exec(args: &ArgMatches) {
+    let result = super::project(args).and_then(|m| m.lsp(io::stdin().lock(), io::stdout().lock()));
+
+    if let Err(e) = result {
+        super::report(&e.into_diagnostics(), MessageFormat::Human);
+        exit(1);
+    }
+}
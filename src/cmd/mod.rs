@@ -1,2 +1,72 @@
+pub mod build;
+pub mod check;
+pub mod clean;
+pub mod fmt;
 pub mod init;
+pub mod lsp;
 pub mod new;
+pub mod parse;
+pub mod serve;
+
+use crate::dir;
+
+use std::io::IsTerminal;
+use std::path::Path;
+
+use clap::ArgMatches;
+
+use mold::{Diagnostic, Error, Mold};
+
+// The project in the current directory, configured by 'mold.conf' and the arguments.
+pub fn project(args: &ArgMatches) -> Result<Mold, Error> {
+    let mut m = Mold::new(dir::OUTPUT);
+
+    m.add_templates_dir(dir::TEMPLATES);
+    m.add_modules_dir(dir::MODULES);
+    m.add_content_dir(dir::CONTENT);
+    m.add_static_dir(dir::STATIC);
+
+    if Path::new(dir::CONFIG).exists() {
+        m.load_config(dir::CONFIG)?;
+    }
+
+    m.include_drafts(args.is_present("drafts"));
+    m.include_future(args.is_present("future"));
+
+    Ok(m)
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum MessageFormat {
+    Human,
+    // One JSON object per line on stdout
+    Json,
+}
+
+impl MessageFormat {
+    pub fn from_args(args: &ArgMatches) -> MessageFormat {
+        match args.value_of("message-format") {
+            Some("json") => MessageFormat::Json,
+            _ => MessageFormat::Human,
+        }
+    }
+}
+
+// Prints the diagnostics, for people to stderr, in colour if it is a terminal,
+// or as JSON to stdout.
+pub fn report(diagnostics: &[Diagnostic], format: MessageFormat) {
+    match format {
+        MessageFormat::Human => {
+            let colour = std::io::stderr().is_terminal();
+
+            for d in diagnostics {
+                eprintln!("{}", d.render(colour));
+            }
+        },
+        MessageFormat::Json => {
+            for d in diagnostics {
+                println!("{}", d.to_json());
+            }
+        },
+    }
+}
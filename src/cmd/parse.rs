@@ -0,0 +1,27 @@
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use super::MessageFormat;
+
+pub fn exec(args: &ArgMatches) {
+    let file = args.value_of("file").unwrap();
+
+    let result = super::project(args).and_then(|m| m.inspect(file, args.is_present("json")));
+
+    let (tree, diagnostics) = match result {
+        Ok(r) => r,
+        Err(e) => {
+            super::report(&e.into_diagnostics(), MessageFormat::Human);
+            exit(1);
+        },
+    };
+
+    print!("{}", tree);
+
+    super::report(&diagnostics, MessageFormat::Human);
+
+    if !diagnostics.is_empty() {
+        exit(1);
+    }
+}
@@ -0,0 +1,188 @@
+use crate::dir;
+
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Component, Path, PathBuf};
+use std::process::exit;
+
+use clap::ArgMatches;
+
+use mold::{Diagnostic, FileSystem, MemoryFs, Mold};
+
+use super::MessageFormat;
+
+pub fn exec(args: &ArgMatches) {
+    let mut m = match super::project(args) {
+        Ok(m) => m,
+        Err(e) => {
+            super::report(&e.into_diagnostics(), MessageFormat::Human);
+            exit(1);
+        },
+    };
+
+    m.include_drafts(!args.is_present("no-drafts"));
+
+    let port = args.value_of("port").unwrap_or_default();
+
+    let listener = match TcpListener::bind(format!("127.0.0.1:{}", port)) {
+        Ok(l) => l,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            exit(1);
+        },
+    };
+
+    println!("Serving on http://{}/", listener.local_addr().unwrap());
+
+    let mut site = MemoryFs::new();
+
+    for stream in listener.incoming() {
+        let result = stream.and_then(|s| respond(&m, &mut site, s));
+
+        if let Err(e) = result {
+            eprintln!("error: {}", e);
+        }
+    }
+}
+
+// Answers a request, after building the site again if a page is requested,
+// so that it shows the latest changes.
+fn respond(m: &Mold, site: &mut MemoryFs, mut stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(&stream);
+
+    let mut request = String::new();
+    reader.read_line(&mut request)?;
+
+    // Read the headers, which aren't needed, so that closing the connection doesn't cut off the request.
+    let mut header = String::new();
+    while reader.read_line(&mut header)? > 2 {
+        header.clear();
+    }
+
+    let mut parts = request.split_whitespace();
+
+    let path = match (parts.next(), parts.next()) {
+        (Some("GET"), Some(target)) => target,
+        _ => return send(&mut stream, "405 Method Not Allowed", "text/plain", b"Method not allowed\n"),
+    };
+
+    let path = match file_path(path) {
+        Some(p) => p,
+        None => return send(&mut stream, "400 Bad Request", "text/plain", b"Bad request\n"),
+    };
+
+    let mut file = Path::new(dir::OUTPUT).join(&path);
+
+    if path.as_os_str().is_empty() || path.extension().is_none_or(|e| e == "html") {
+        match build(m) {
+            Ok(built) => *site = built,
+            Err(d) => {
+                let errors: Vec<_> = d.iter().map(|d| d.render(false)).collect();
+                return send(&mut stream, "500 Internal Server Error", "text/plain", errors.join("\n").as_bytes());
+            },
+        }
+    }
+
+    if site.is_dir(&file) {
+        file.push("index.html");
+    }
+
+    match site.read(&file) {
+        Ok(content) => send(&mut stream, "200 OK", content_type(&file), &content),
+        Err(_) => send(&mut stream, "404 Not Found", "text/plain", b"Not found\n"),
+    }
+}
+
+// Builds the site in memory, and reports the warnings, or else returns the errors.
+fn build(m: &Mold) -> Result<MemoryFs, Vec<Diagnostic>> {
+    let site = MemoryFs::new();
+
+    match m.build_to(&site) {
+        Ok(warnings) => {
+            super::report(&warnings, MessageFormat::Human);
+            Ok(site)
+        },
+        Err(e) => {
+            let diagnostics = e.into_diagnostics();
+            super::report(&diagnostics, MessageFormat::Human);
+            Err(diagnostics)
+        },
+    }
+}
+
+fn send(stream: &mut TcpStream, status: &str, content_type: &str, body: &[u8]) -> io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status, content_type, body.len()
+    )?;
+
+    stream.write_all(body)?;
+    stream.flush()
+}
+
+// The path of a file in the output directory which a request is for, without the query.
+// Paths leaving the output directory, e.g. with '..', are refused.
+fn file_path(target: &str) -> Option<PathBuf> {
+    let target = target.split(['?', '#']).next().unwrap_or_default();
+    let path = PathBuf::from(decode(target)?.trim_start_matches('/'));
+
+    if !path.components().all(|c| matches!(c, Component::Normal(..))) {
+        return None;
+    }
+
+    Some(path)
+}
+
+// Decodes percent-encoded bytes, e.g. '%20'.
+fn decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = s.as_bytes();
+
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+
+    String::from_utf8(bytes).ok()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path.extension().and_then(|e| e.to_str()).unwrap_or_default() {
+        "html" => "text/html; charset=utf-8",
+        "css" => "text/css",
+        "js" => "text/javascript",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "txt" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn paths_of_requests() {
+        assert_eq!(file_path("/"), Some(PathBuf::new()));
+        assert_eq!(file_path("/posts/a.html?x=1"), Some(PathBuf::from("posts/a.html")));
+        assert_eq!(file_path("/my%20post.html"), Some(PathBuf::from("my post.html")));
+        assert_eq!(file_path("/../mold.conf"), None);
+        assert_eq!(file_path("/posts/%2e%2e/%2e%2e/mold.conf"), None);
+        assert_eq!(file_path("/%zz"), None);
+    }
+}
@@ -5,6 +5,11 @@ pub const MODULES: &str = "modules/";
 pub const CONTENT: &str = "content/";
 pub const STATIC: &str = "static/";
 
+// The built site
+pub const OUTPUT: &str = "output/";
+pub const CONFIG: &str = "mold.conf";
+
+#[allow(clippy::useless_vec)]
 pub fn directories() -> Vec<PathBuf> {
     let dirs = vec![
         Path::new(TEMPLATES),
@@ -4,12 +4,20 @@ mod dir;
 use clap::{App, SubCommand, Arg};
 use clap::{crate_name, crate_version};
 
+#[allow(clippy::needless_return)]
 fn main() {
     let matches = cli().get_matches();
 
     match matches.subcommand() {
+        ("build", Some(sub_matches)) => cmd::build::exec(sub_matches),
+        ("check", Some(sub_matches)) => cmd::check::exec(sub_matches),
+        ("clean", Some(sub_matches)) => cmd::clean::exec(sub_matches),
+        ("fmt", Some(sub_matches)) => cmd::fmt::exec(sub_matches),
         ("init", Some(sub_matches)) => cmd::init::exec(sub_matches),
+        ("lsp", Some(sub_matches)) => cmd::lsp::exec(sub_matches),
         ("new", Some(sub_matches)) => cmd::new::exec(sub_matches),
+        ("parse", Some(sub_matches)) => cmd::parse::exec(sub_matches),
+        ("serve", Some(sub_matches)) => cmd::serve::exec(sub_matches),
         _ => {
             cli().print_help().unwrap();
             return;
@@ -20,8 +28,52 @@ fn main() {
 fn cli() -> App<'static, 'static> {
     let app = App::new(crate_name!())
         .version(crate_version!())
+        .subcommand(SubCommand::with_name("build")
+            .arg(Arg::with_name("drafts").long("drafts")
+                .help("Includes pages with 'draft: true'"))
+            .arg(Arg::with_name("future").long("future")
+                .help("Includes pages dated in the future"))
+            .arg(message_format()))
+        .subcommand(SubCommand::with_name("check")
+            .about("Reports problems in the project without building it")
+            .arg(Arg::with_name("drafts").long("drafts")
+                .help("Includes pages with 'draft: true'"))
+            .arg(Arg::with_name("future").long("future")
+                .help("Includes pages dated in the future"))
+            .arg(message_format()))
+        .subcommand(SubCommand::with_name("clean")
+            .about("Removes the built site"))
+        .subcommand(SubCommand::with_name("fmt")
+            .about("Formats templates, modules and content")
+            .arg(Arg::with_name("check").long("check")
+                .help("Lists the files which aren't formatted instead of formatting them")))
         .subcommand(SubCommand::with_name("init"))
+        .subcommand(SubCommand::with_name("lsp")
+            .about("Runs a language server for editors over stdin and stdout"))
         .subcommand(SubCommand::with_name("new")
-            .arg(Arg::with_name("name").required(true)));
+            .arg(Arg::with_name("name").required(true)))
+        .subcommand(SubCommand::with_name("parse")
+            .about("Prints the tree a file is parsed into")
+            .arg(Arg::with_name("file").required(true))
+            .arg(Arg::with_name("json").long("json")
+                .help("Prints the tree as JSON")))
+        .subcommand(SubCommand::with_name("serve")
+            .about("Serves the site on localhost, built again for every page, drafts included")
+            .arg(Arg::with_name("port").long("port")
+                .takes_value(true)
+                .default_value("8000")
+                .help("The port to listen on, 0 picks a free one"))
+            .arg(Arg::with_name("no-drafts").long("no-drafts")
+                .help("Leaves out pages with 'draft: true'"))
+            .arg(Arg::with_name("future").long("future")
+                .help("Includes pages dated in the future")));
     app
 }
+
+fn message_format() -> Arg<'static, 'static> {
+    Arg::with_name("message-format").long("message-format")
+        .takes_value(true)
+        .possible_values(&["human", "json"])
+        .default_value("human")
+        .help("How errors and warnings are printed, 'json' prints one object per line to stdout")
+}
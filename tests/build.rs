@@ -0,0 +1,496 @@
+use std::fs::{create_dir, create_dir_all, read_to_string, remove_file, write};
+use std::path::Path;
+use std::process::Command;
+
+use mold::{FeedContent, Mold, Value};
+
+use tempfile::Builder;
+
+fn setup(root: &Path) {
+    for d in &["templates", "modules", "content/posts"] {
+        create_dir_all(root.join(d)).unwrap();
+    }
+
+    write(root.join("templates/base.html"), "<title><<page.title>></title>\n<<content>>\n").unwrap();
+    write(root.join("modules/quote.html"), "<blockquote><<content>></blockquote>").unwrap();
+
+    write(root.join("content/index.html"), "\
+---
+title: Home
+---
+<<base<<<<content<<
+<<for post in posts<<<a href=\"<<post.url>>\"><<post.title>></a><<if post.draft<< (draft)>>if>>
+>>else<<No posts>>for>>
+>>content>>>>base>>
+").unwrap();
+
+    write(root.join("content/posts/a.html"), "\
+---
+title: First
+---
+<<base<<<<content<<<<quote<<Hi>>quote>>>>content>>>>base>>
+").unwrap();
+
+    write(root.join("content/posts/b.html"), "\
+---
+title: Second
+draft: true
+---
+<<base<<<<content<<Bye>>content>>>>base>>
+").unwrap();
+}
+
+fn mold(root: &Path, out: &Path) -> Mold {
+    let mut m = Mold::new(out);
+
+    m.add_templates_dir(root.join("templates"));
+    m.add_modules_dir(root.join("modules"));
+    m.add_content_dir(root.join("content"));
+
+    m
+}
+
+#[test]
+fn renders_pages_with_loops_and_modules() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    let mut m = mold(root.path(), &out);
+    m.include_drafts(true);
+    m.build().unwrap();
+
+    assert_eq!(read_to_string(out.join("index.html")).unwrap(), "\
+<title>Home</title>
+
+<a href=\"/posts/a.html\">First</a>
+<a href=\"/posts/b.html\">Second</a> (draft)
+
+
+");
+
+    assert_eq!(
+        read_to_string(out.join("posts/a.html")).unwrap(),
+        "<title>First</title>\n<blockquote>Hi</blockquote>\n"
+    );
+}
+
+#[test]
+fn unknown_collection_fails_the_build() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    create_dir(root.path().join("content/other")).unwrap();
+    write(root.path().join("content/other/c.html"), "<<for x in nothing<<>>for>>").unwrap();
+
+    let err = mold(root.path(), &out).build().unwrap_err();
+
+    assert_eq!(
+        format!("{}", err),
+        format!("{}: error: unknown collection 'nothing'", root.path().join("content/other/c.html").display())
+    );
+}
+
+#[test]
+fn errors_of_all_files_are_reported() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("content/b.html"), "<<x<<\n>>y>>\n>>z>>").unwrap();
+    write(root.path().join("content/a.html"), "---\ntitle: a\nnonsense\n---\n").unwrap();
+    write(root.path().join("content/c.html"), "<<for x in nothing<<>>for>>").unwrap();
+    write(root.path().join("modules/m.html"), "<<content<<").unwrap();
+
+    let err = mold(root.path(), &out).build().unwrap_err();
+
+    let d: Vec<String> = err.into_diagnostics().iter().map(|d| {
+        let path = d.path.strip_prefix(root.path()).unwrap();
+        format!("{}:{}: {}", path.display(), d.line.unwrap(), d.message)
+    }).collect();
+
+    // Render errors are only found once all files can be parsed.
+    assert_eq!(d, vec![
+        "content/a.html:3: invalid front matter on line 3",
+        "content/b.html:2: closing tag does not match the opening tag",
+        "content/b.html:3: closing tag without an opening tag",
+        "modules/m.html:1: opening tag without a closing tag",
+    ]);
+}
+
+#[test]
+fn custom_filters() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("content/about.html"), "\
+---
+title: About
+date: 2020-10-01
+---
+<<page.title | shout>> <<page.date | date \"%d %B %Y\">>").unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.add_filter("shout", |v, args| {
+        if !args.is_empty() {
+            return Err("takes no arguments".to_owned());
+        }
+
+        Ok(Value::String(format!("{}!", v.to_string().to_uppercase())))
+    });
+
+    m.build().unwrap();
+
+    assert_eq!(read_to_string(out.join("about.html")).unwrap(), "ABOUT! 01 October 2020");
+}
+
+#[test]
+fn taxonomy_pages() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("templates/taxonomy.html"), "\
+<<for term in taxonomy.terms<<<<term.name>>: <<term.url>>
+>>for>>").unwrap();
+    write(root.path().join("templates/term.html"), "\
+<<page.title>> in <<taxonomy.name>>:<<for post in term.pages<< <<post.title>>>>for>>").unwrap();
+
+    write(root.path().join("content/posts/c.html"), "\
+---
+title: Third
+tags: [Rust, Web Dev]
+---
+<<for tag in page.terms.tags<<<a href=\"<<tag.url>>\"><<tag.name>></a>
+>>for>>").unwrap();
+
+    write(root.path().join("content/posts/d.html"), "\
+---
+title: Fourth
+tags: [rust]
+---
+").unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.add_taxonomy("tags");
+    m.build().unwrap();
+
+    assert_eq!(
+        read_to_string(out.join("posts/c.html")).unwrap(),
+        "<a href=\"/tags/rust/\">Rust</a>\n<a href=\"/tags/web-dev/\">Web Dev</a>\n"
+    );
+    assert_eq!(
+        read_to_string(out.join("tags/index.html")).unwrap(),
+        "Rust: /tags/rust/\nWeb Dev: /tags/web-dev/\n"
+    );
+    assert_eq!(read_to_string(out.join("tags/rust/index.html")).unwrap(), "Rust in tags: Third Fourth");
+}
+
+#[test]
+fn taxonomy_without_template() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    let mut m = mold(root.path(), &out);
+    m.add_taxonomy("categories");
+
+    let err = m.build().unwrap_err();
+
+    assert_eq!(format!("{}", err), concat!(
+        "error: missing template 'categories' or 'taxonomy'\n",
+        "error: missing template 'categories-term' or 'term'",
+    ));
+}
+
+#[test]
+fn feeds_of_collection() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("content/posts/a.html"), "\
+---
+title: First & Best
+date: 2020-10-01
+---
+<<base<<<<content<<<p>Hi</p>
+>>content>>>>base>>
+").unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.set_title("Blog");
+    m.set_base_url("https://example.com/");
+    m.add_feed("posts", FeedContent::Full);
+    m.build().unwrap();
+
+    let rss = read_to_string(out.join("posts/rss.xml")).unwrap();
+    assert!(rss.contains("<title>First &amp; Best</title>"));
+    assert!(rss.contains("<link>https://example.com/posts/a.html</link>"));
+    assert!(rss.contains("<pubDate>Thu, 1 Oct 2020 00:00:00 +0000</pubDate>"));
+    assert!(rss.contains("<description>&lt;p&gt;Hi&lt;/p&gt;\n</description>"));
+
+    let atom = read_to_string(out.join("posts/atom.xml")).unwrap();
+    assert!(atom.contains("<updated>2020-10-01T00:00:00+00:00</updated>"));
+}
+
+#[test]
+fn feed_template() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("templates/rss.xml"), "\
+<<for e in feed.entries<<<<e.title | escape>> <<e.url>>
+>>for>>").unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.set_base_url("https://example.com");
+    m.add_feed("posts", FeedContent::Summary);
+    m.build().unwrap();
+
+    assert_eq!(
+        read_to_string(out.join("posts/rss.xml")).unwrap(),
+        "First https://example.com/posts/a.html\n"
+    );
+}
+
+#[test]
+fn feeds_require_base_url() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    let mut m = mold(root.path(), &out);
+    m.add_feed("posts", FeedContent::Full);
+
+    assert_eq!(format!("{}", m.build().unwrap_err()), "feeds require a base url");
+}
+
+#[test]
+fn build_command_reads_config() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+    create_dir(root.path().join("static")).unwrap();
+    create_dir(root.path().join("static/css")).unwrap();
+    write(root.path().join("static/css/site.css"), "body {}").unwrap();
+
+    write(root.path().join("mold.conf"), "\
+title: Blog
+base_url: https://example.com
+feeds: [posts]
+author: Me
+").unwrap();
+    write(root.path().join("content/about.html"), "<<site.title>> by <<site.author>>").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mold"))
+        .arg("build")
+        .current_dir(&root)
+        .output().expect("Failed to run binary");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+
+    let out = root.path().join("output");
+    assert_eq!(read_to_string(out.join("about.html")).unwrap(), "Blog by Me");
+    assert!(out.join("posts/atom.xml").exists());
+    assert!(out.join("css/site.css").exists());
+}
+
+#[test]
+fn build_command_prints_warnings_as_json() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+    create_dir(root.path().join("static")).unwrap();
+    write(root.path().join("content/about.html"), "<<page.titel>>").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mold"))
+        .args(["build", "--message-format", "json"])
+        .current_dir(&root)
+        .output().expect("Failed to run binary");
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), concat!(
+        r#"{"code":"W001","column":null,"file":"content/about.html","hint":null,"line":null,"#,
+        r#""message":"unknown variable 'page.titel'","related":[],"severity":"warning","span":null}"#,
+        "\n",
+    ));
+}
+
+#[test]
+fn sitemap_and_robots() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("content/secret.html"), "\
+---
+sitemap: false
+---
+").unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.set_base_url("https://example.com");
+    m.build().unwrap();
+
+    let sitemap = read_to_string(out.join("sitemap.xml")).unwrap();
+    assert!(sitemap.contains("<loc>https://example.com/index.html</loc>\n<lastmod>"));
+    assert!(sitemap.contains("<loc>https://example.com/posts/a.html</loc>"));
+    assert!(!sitemap.contains("secret"));
+
+    let robots = read_to_string(out.join("robots.txt")).unwrap();
+    assert!(robots.contains("Sitemap: https://example.com/sitemap.xml"));
+}
+
+#[test]
+fn robots_of_project_is_kept() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    create_dir(root.path().join("static")).unwrap();
+    write(root.path().join("static/robots.txt"), "User-agent: *\nDisallow: /\n").unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.add_static_dir(root.path().join("static"));
+    m.set_base_url("https://example.com");
+    m.build().unwrap();
+
+    assert_eq!(read_to_string(out.join("robots.txt")).unwrap(), "User-agent: *\nDisallow: /\n");
+    assert!(out.join("sitemap.xml").exists());
+}
+
+#[test]
+fn drafts_and_future_pages() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("content/posts/later.html"), "\
+---
+title: Later
+date: 2999-01-01
+---
+<<if page.unpublished<<UNPUBLISHED>>if>>").unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.set_base_url("https://example.com");
+    m.add_feed("posts", FeedContent::Full);
+    m.build().unwrap();
+
+    assert!(!out.join("posts/b.html").exists());
+    assert!(!out.join("posts/later.html").exists());
+
+    let index = read_to_string(out.join("index.html")).unwrap();
+    assert!(index.contains("First"));
+    assert!(!index.contains("Second"));
+    assert!(!read_to_string(out.join("sitemap.xml")).unwrap().contains("later"));
+    assert!(!read_to_string(out.join("posts/rss.xml")).unwrap().contains("Second"));
+
+    let out = root.path().join("output-all");
+
+    let mut m = mold(root.path(), &out);
+    m.include_drafts(true);
+    m.include_future(true);
+    m.build().unwrap();
+
+    assert!(out.join("posts/b.html").exists());
+    assert_eq!(read_to_string(out.join("posts/later.html")).unwrap(), "UNPUBLISHED");
+}
+
+#[test]
+fn collection_of_only_drafts() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    remove_file(root.path().join("content/posts/a.html")).unwrap();
+
+    let mut m = mold(root.path(), &out);
+    m.set_base_url("https://example.com");
+    m.add_feed("posts", FeedContent::Full);
+    m.build().unwrap();
+
+    assert!(read_to_string(out.join("index.html")).unwrap().contains("No posts"));
+    assert!(!read_to_string(out.join("posts/rss.xml")).unwrap().contains("<item>"));
+    assert!(m.check().unwrap().is_empty());
+}
+
+#[test]
+fn failed_build_keeps_previous_output() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    mold(root.path(), &out).build().unwrap();
+    assert!(out.join("posts/a.html").exists());
+
+    write(root.path().join("content/posts/a.html"), "<<for x in nothing<<>>for>>").unwrap();
+    write(root.path().join("content/posts/new.html"), "new").unwrap();
+
+    assert!(mold(root.path(), &out).build().is_err());
+
+    assert!(out.join("posts/a.html").exists());
+    assert!(!out.join("posts/new.html").exists());
+
+    // Only the manifest of the previous build is left in the cache.
+    let entries: Vec<_> = root.path().join(".mold").read_dir().unwrap()
+        .map(|e| e.unwrap().file_name().into_string().unwrap())
+        .collect();
+    assert_eq!(entries, ["manifest"]);
+}
+
+#[test]
+fn renamed_page_does_not_linger() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    mold(root.path(), &out).build().unwrap();
+
+    std::fs::rename(root.path().join("content/posts/a.html"), root.path().join("content/posts/first.html")).unwrap();
+
+    mold(root.path(), &out).build().unwrap();
+
+    assert!(!out.join("posts/a.html").exists());
+    assert!(out.join("posts/first.html").exists());
+
+    let manifest = read_to_string(root.path().join(".mold/manifest")).unwrap();
+    assert!(manifest.contains("posts/first.html\n"));
+    assert!(!manifest.contains("posts/a.html"));
+}
+
+#[test]
+fn render_errors_of_all_pages_are_reported() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+    let out = root.path().join("output");
+
+    setup(root.path());
+
+    write(root.path().join("content/x.html"), "<<for a in nothing<<>>for>>").unwrap();
+    write(root.path().join("content/y.html"), "<<page.title | shout>>").unwrap();
+
+    let d = mold(root.path(), &out).build().unwrap_err().into_diagnostics();
+
+    assert_eq!(d.len(), 2);
+    assert_eq!(d[0].message, "unknown collection 'nothing'");
+    assert_eq!(d[1].message, "unknown filter 'shout'");
+}
@@ -0,0 +1,176 @@
+use std::fs::{create_dir_all, write};
+use std::path::Path;
+use std::process::Command;
+
+use mold::{Mold, Severity};
+
+use tempfile::Builder;
+
+fn setup(root: &Path) {
+    for d in &["templates", "modules", "content", "static"] {
+        create_dir_all(root.join(d)).unwrap();
+    }
+
+    write(root.join("templates/base.html"), "<title><<page.title>></title>\n<<content>>\n").unwrap();
+    write(root.join("modules/quote.html"), "<blockquote><<content>></blockquote>").unwrap();
+
+    write(root.join("content/index.html"), "\
+---
+title: Home
+---
+<<base<<<<content<<<<quote<<Hi>>quote>>>>content>>>>base>>
+").unwrap();
+}
+
+fn mold(root: &Path) -> Mold {
+    let mut m = Mold::new(root.join("output"));
+
+    m.add_templates_dir(root.join("templates"));
+    m.add_modules_dir(root.join("modules"));
+    m.add_content_dir(root.join("content"));
+    m.add_static_dir(root.join("static"));
+
+    m
+}
+
+#[test]
+fn valid_project_has_no_diagnostics() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    assert_eq!(mold(root.path()).check().unwrap(), vec![]);
+    assert!(!root.path().join("output").exists());
+}
+
+#[test]
+fn reports_errors_of_all_files() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    write(root.path().join("modules/broken.html"), "<p>\n<<content<<\n").unwrap();
+    write(root.path().join("content/about.html"), "\
+---
+title: About
+---
+<<base<<<<content<<
+About>>contnt>>
+>>base>>
+").unwrap();
+    write(root.path().join("templates/a.html"), "<<b<<x>>b>>").unwrap();
+    write(root.path().join("templates/b.html"), "<<a<<y>>a>>").unwrap();
+
+    let d = mold(root.path()).check().unwrap();
+
+    let lines: Vec<String> = d.iter().map(|d| {
+        let path = d.path.strip_prefix(root.path()).unwrap();
+        format!("{}:{}: {}", path.display(), d.line.unwrap(), d.message)
+    }).collect();
+
+    assert_eq!(lines, vec![
+        "content/about.html:5: closing tag does not match the opening tag",
+        "modules/broken.html:2: opening tag without a closing tag",
+        "templates/a.html:1: template 'a' extends itself",
+        "templates/b.html:1: template 'b' extends itself",
+    ]);
+}
+
+#[test]
+fn warns_about_unknown_variables_and_templates() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    write(root.path().join("content/about.html"), "\
+---
+title: About
+---
+<<page.titel>>
+").unwrap();
+    write(root.path().join("content/contact.html"), "<<bsae<<<<content<<Hi>>content>>>>bsae>>\n").unwrap();
+
+    let d = mold(root.path()).check().unwrap();
+
+    assert_eq!(d.len(), 2);
+    assert!(d.iter().all(|d| d.severity == Severity::Warning));
+    assert_eq!(d[0].message, "unknown variable 'page.titel'");
+    assert_eq!(d[1].message, "extends 'bsae', which is not a template");
+    assert_eq!(d[1].line, Some(1));
+}
+
+#[test]
+fn reports_render_errors() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    write(root.path().join("content/posts.html"), "<<for post in posts<<x>>for>>").unwrap();
+
+    let d = mold(root.path()).check().unwrap();
+
+    assert_eq!(d.len(), 1);
+    assert!(d[0].is_error());
+    assert!(d[0].path.ends_with("content/posts.html"));
+    assert_eq!(d[0].message, "unknown collection 'posts'");
+}
+
+#[test]
+fn cli_exits_with_failure_on_errors() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    let bin = env!("CARGO_BIN_EXE_mold");
+
+    let output = Command::new(bin)
+        .arg("check")
+        .current_dir(&root)
+        .output().expect("Failed to run binary");
+
+    assert!(output.status.success());
+
+    write(root.path().join("content/about.html"), "a\n<<b<<c\n").unwrap();
+
+    let output = Command::new(bin)
+        .arg("check")
+        .current_dir(&root)
+        .output().expect("Failed to run binary");
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("\
+error: opening tag without a closing tag
+ --> content/about.html:2:1
+  |
+2 | <<b<<c
+  | ^^^^^
+  = hint: close the tag with '>>b>>'
+"));
+    assert!(!root.path().join("output").exists());
+}
+
+#[test]
+fn cli_prints_json() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    write(root.path().join("content/about.html"), "---\ntitle: a\n---\n<<page.titel>>\n>>x>>").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mold"))
+        .args(["check", "--message-format", "json"])
+        .current_dir(&root)
+        .output().expect("Failed to run binary");
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    assert!(!output.status.success());
+    assert_eq!(stdout, concat!(
+        r#"{"code":"E001","column":1,"file":"content/about.html","#,
+        r#""hint":"remove the closing tag, or add an opening tag before it","line":5,"#,
+        r#""message":"closing tag without an opening tag","related":[],"severity":"error","span":{"end":37,"start":32}}"#,
+        "\n",
+    ));
+}
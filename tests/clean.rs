@@ -0,0 +1,50 @@
+use std::fs::{create_dir_all, write};
+use std::process::Command;
+
+use tempfile::Builder;
+
+#[test]
+fn removes_built_site() {
+    let temp_dir = Builder::new().prefix("mold").tempdir().expect("Could not create temporary directory");
+
+    let bin = env!("CARGO_BIN_EXE_mold");
+
+    for d in &["templates", "modules", "content", "static"] {
+        create_dir_all(temp_dir.path().join(d)).unwrap();
+    }
+    write(temp_dir.path().join("content/index.html"), "Hello").unwrap();
+
+    let output = Command::new(bin)
+        .arg("build")
+        .current_dir(&temp_dir)
+        .output().expect("Failed to run binary");
+
+    assert!(output.status.success());
+    assert!(temp_dir.path().join("output/index.html").exists());
+
+    let output = Command::new(bin)
+        .arg("clean")
+        .current_dir(&temp_dir)
+        .output().expect("Failed to run binary");
+
+    assert!(output.status.success());
+    assert!(!temp_dir.path().join("output").exists());
+}
+
+#[test]
+fn refuses_to_remove_unknown_output() {
+    let temp_dir = Builder::new().prefix("mold").tempdir().expect("Could not create temporary directory");
+
+    let bin = env!("CARGO_BIN_EXE_mold");
+
+    create_dir_all(temp_dir.path().join("output")).unwrap();
+    write(temp_dir.path().join("output/important.txt"), "").unwrap();
+
+    let output = Command::new(bin)
+        .arg("clean")
+        .current_dir(&temp_dir)
+        .output().expect("Failed to run binary");
+
+    assert!(!output.status.success());
+    assert!(temp_dir.path().join("output/important.txt").exists());
+}
@@ -0,0 +1,95 @@
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::Path;
+use std::process::Command;
+
+use tempfile::Builder;
+
+const PAGE: &str = "\
+---
+title: Home
+---
+<< base <<<<title<<Hi>>title>>
+<< content<<
+<p><< page.title >></p>
+<<quote<<>>quote>>
+>>content >>>>base>>
+";
+
+fn setup(root: &Path) {
+    for d in &["templates", "modules", "content", "static"] {
+        create_dir_all(root.join(d)).unwrap();
+    }
+
+    write(root.join("templates/base.html"), "<title><<title>></title>\n<<content>>\n").unwrap();
+    write(root.join("modules/quote.html"), "<blockquote><< content >></blockquote>").unwrap();
+    write(root.join("content/index.html"), PAGE).unwrap();
+}
+
+fn mold(root: &Path, args: &[&str]) -> (bool, String) {
+    let output = Command::new(env!("CARGO_BIN_EXE_mold"))
+        .args(args)
+        .current_dir(root)
+        .output().expect("Failed to run binary");
+
+    (output.status.success(), String::from_utf8(output.stdout).unwrap())
+}
+
+#[test]
+fn formats_files_without_changing_the_site() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    assert!(mold(root.path(), &["build"]).0);
+    let before = read_to_string(root.path().join("output/index.html")).unwrap();
+
+    assert!(mold(root.path(), &["fmt"]).0);
+
+    assert_eq!(read_to_string(root.path().join("content/index.html")).unwrap(), "\
+---
+title: Home
+---
+<<base<<
+  <<title<<Hi>>title>>
+  <<content<<
+    <p><<page.title>></p>
+    <<quote<<>>quote>>
+  >>content>>
+>>base>>
+");
+    assert_eq!(read_to_string(root.path().join("modules/quote.html")).unwrap(), "<blockquote><<content>></blockquote>");
+
+    // Only the indentation of lines changes.
+    let unindented = |s: &str| s.lines().map(|l| l.trim_start().to_owned()).collect::<Vec<_>>();
+
+    assert!(mold(root.path(), &["build"]).0);
+    assert_eq!(unindented(&read_to_string(root.path().join("output/index.html")).unwrap()), unindented(&before));
+}
+
+#[test]
+fn check_lists_unformatted_files() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    let (success, stdout) = mold(root.path(), &["fmt", "--check"]);
+
+    assert!(!success);
+    assert_eq!(stdout, "content/index.html\nmodules/quote.html\n");
+    assert_eq!(read_to_string(root.path().join("content/index.html")).unwrap(), PAGE);
+
+    assert!(mold(root.path(), &["fmt"]).0);
+    assert_eq!(mold(root.path(), &["fmt", "--check"]), (true, String::new()));
+}
+
+#[test]
+fn leaves_files_with_errors() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    write(root.path().join("content/about.html"), "<< a <<").unwrap();
+
+    assert!(!mold(root.path(), &["fmt"]).0);
+    assert_eq!(read_to_string(root.path().join("content/index.html")).unwrap(), PAGE);
+}
@@ -0,0 +1,128 @@
+use std::fs::{create_dir_all, write};
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use tempfile::Builder;
+
+fn setup(root: &Path) {
+    for d in &["templates", "modules", "content", "static"] {
+        create_dir_all(root.join(d)).unwrap();
+    }
+
+    write(root.join("templates/base.html"), "<title><<title>></title>\n<<content>>\n").unwrap();
+    write(root.join("templates/post.html"), "<<base<<<<content<<<article><<body>></article>>>content>>>>base>>\n").unwrap();
+    write(root.join("modules/quote.html"), "<blockquote><<content>></blockquote>").unwrap();
+}
+
+// Sends the messages to 'mold lsp' and returns the bodies of the messages it sent back.
+fn lsp(root: &Path, messages: &[String]) -> Vec<String> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mold"))
+        .arg("lsp")
+        .current_dir(root)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn().expect("Failed to run binary");
+
+    let stdin = child.stdin.as_mut().unwrap();
+    for m in messages {
+        write!(stdin, "Content-Length: {}\r\n\r\n{}", m.len(), m).unwrap();
+    }
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+
+    stdout.split("Content-Length: ").skip(1).map(|m| {
+        let (length, body) = m.split_once("\r\n\r\n").unwrap();
+        assert_eq!(length.parse::<usize>().unwrap(), body.len());
+        body.to_owned()
+    }).collect()
+}
+
+fn request(id: usize, method: &str, params: &str) -> String {
+    format!(r#"{{"jsonrpc":"2.0","id":{},"method":"{}","params":{}}}"#, id, method, params)
+}
+
+fn notify(method: &str, params: &str) -> String {
+    format!(r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#, method, params)
+}
+
+#[test]
+fn answers_requests() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    let root = root.path().canonicalize().unwrap();
+    let uri = format!("file://{}/content/index.html", root.display());
+    let text = r#"---\ntitle: Home\n---\n<<post<<\n  <<body<<<<quote<<Hi>>quote>>>>body>>\n>>post>>\n"#;
+    let at = |line, character| format!(
+        r#"{{"textDocument":{{"uri":"{}"}},"position":{{"line":{},"character":{}}}}}"#, uri, line, character);
+
+    let responses = lsp(&root, &[
+        request(1, "initialize", r#"{"capabilities":{}}"#),
+        notify("initialized", "{}"),
+        notify("textDocument/didOpen", &format!(
+            r#"{{"textDocument":{{"uri":"{}","languageId":"html","version":1,"text":"{}"}}}}"#, uri, text)),
+        request(2, "textDocument/definition", &at(3, 3)),
+        request(3, "textDocument/definition", &at(4, 15)),
+        request(4, "textDocument/hover", &at(3, 3)),
+        request(5, "textDocument/completion", &at(4, 2)),
+        request(6, "textDocument/documentSymbol", &at(0, 0)),
+        request(7, "textDocument/unknown", "{}"),
+        request(8, "shutdown", "null"),
+        notify("exit", "null"),
+    ]);
+
+    assert_eq!(responses.len(), 9);
+    assert!(responses[0].contains(r#""definitionProvider":true"#));
+
+    // No errors
+    assert!(responses[1].contains(r#""method":"textDocument/publishDiagnostics""#));
+    assert!(responses[1].contains(r#""diagnostics":[]"#));
+
+    let location = |path: &str| format!(
+        r#""result":{{"range":{{"end":{{"character":0,"line":0}},"start":{{"character":0,"line":0}}}},"uri":"file://{}/{}"}}"#,
+        root.display(), path);
+    assert!(responses[2].contains(&location("templates/post.html")), "{}", responses[2]);
+    assert!(responses[3].contains(&location("modules/quote.html")), "{}", responses[3]);
+
+    assert!(responses[4].contains("template `post` → `base`"), "{}", responses[4]);
+
+    assert!(responses[5].contains(r#""label":"title""#), "{}", responses[5]);
+    assert!(responses[5].contains(r#""label":"content""#), "{}", responses[5]);
+
+    assert!(responses[6].contains(r#""name":"post""#), "{}", responses[6]);
+    assert!(responses[6].contains(r#""name":"body""#), "{}", responses[6]);
+
+    assert!(responses[7].contains(r#""code":-32601"#), "{}", responses[7]);
+    assert!(responses[8].contains(r#""id":8,"jsonrpc":"2.0","result":null"#), "{}", responses[8]);
+}
+
+#[test]
+fn publishes_errors_as_you_type() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    let root = root.path().canonicalize().unwrap();
+    let uri = format!("file://{}/templates/page.html", root.display());
+
+    let responses = lsp(&root, &[
+        notify("textDocument/didOpen", &format!(
+            r#"{{"textDocument":{{"uri":"{}","languageId":"html","version":1,"text":"<<a<<"}}}}"#, uri)),
+        notify("textDocument/didChange", &format!(
+            r#"{{"textDocument":{{"uri":"{}","version":2}},"contentChanges":[{{"text":"<<a<<x>>a>>"}}]}}"#, uri)),
+        notify("exit", "null"),
+    ]);
+
+    assert_eq!(responses.len(), 2);
+    assert!(responses[0].contains(concat!(
+        r#""diagnostics":[{"code":"E002","message":"opening tag without a closing tag\nhint: close the tag with '>>a>>'","#,
+        r#""range":{"end":{"character":5,"line":0},"start":{"character":0,"line":0}},"#,
+        r#""relatedInformation":[],"severity":1,"source":"mold"}]"#,
+    )), "{}", responses[0]);
+    assert!(responses[1].contains(r#""diagnostics":[]"#), "{}", responses[1]);
+}
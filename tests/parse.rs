@@ -0,0 +1,45 @@
+use std::fs::{create_dir_all, write};
+use std::process::Command;
+
+use tempfile::Builder;
+
+#[test]
+fn prints_tree_of_page() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    for d in &["templates", "modules", "content", "static"] {
+        create_dir_all(root.path().join(d)).unwrap();
+    }
+
+    write(root.path().join("content/index.html"), "---\ntitle: a\n---\n<<base<<<<content<<Hi>>content>>>>base>>\n").unwrap();
+
+    let bin = env!("CARGO_BIN_EXE_mold");
+
+    let output = Command::new(bin)
+        .args(["parse", "content/index.html"])
+        .current_dir(&root)
+        .output().expect("Failed to run binary");
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "\
+tag 'base' 17..57
+  tag 'content' 25..49
+    text 36..38 \"Hi\"
+text 57..58 \"\\n\"
+");
+
+    write(root.path().join("templates/base.html"), "<<a>>>>b>>").unwrap();
+
+    let output = Command::new(bin)
+        .args(["parse", "templates/base.html", "--json"])
+        .current_dir(&root)
+        .output().expect("Failed to run binary");
+
+    assert!(!output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), concat!(
+        r#"[{"args":"","branches":[],"content":[],"name":"a","self_closing":true,"span":{"end":5,"start":0},"type":"tag"},"#,
+        r#"{"span":{"end":10,"start":5},"text":">>b>>","type":"invalid"}]"#,
+        "\n",
+    ));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("error: closing tag without an opening tag"));
+}
@@ -0,0 +1,98 @@
+use std::fs::{create_dir_all, write};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::Path;
+use std::process::{Child, Command, Stdio};
+
+use tempfile::Builder;
+
+fn setup(root: &Path) {
+    for d in &["templates", "modules", "content/posts", "static"] {
+        create_dir_all(root.join(d)).unwrap();
+    }
+
+    write(root.join("templates/base.html"), "<<if page.unpublished<<[draft] >>if>><<content>>").unwrap();
+    write(root.join("content/index.html"), "<<for post in posts<<<<post.title>> >>for>>").unwrap();
+
+    write(root.join("content/posts/a.html"), "\
+---
+title: Staged
+draft: true
+---
+<<base<<<<content<<Soon>>content>>>>base>>
+").unwrap();
+}
+
+// Starts 'mold serve' on a free port, and returns the address it serves on.
+fn serve(root: &Path, args: &[&str]) -> (Child, String) {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mold"))
+        .args(["serve", "--port", "0"])
+        .args(args)
+        .current_dir(root)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn().expect("Failed to run binary");
+
+    let mut line = String::new();
+    BufReader::new(child.stdout.as_mut().unwrap()).read_line(&mut line).unwrap();
+
+    let address = line.trim().trim_start_matches("Serving on http://").trim_end_matches('/').to_owned();
+
+    (child, address)
+}
+
+fn get(address: &str, path: &str) -> String {
+    let mut stream = TcpStream::connect(address).unwrap();
+    let request = format!("GET {} HTTP/1.1\r\nHost: {}\r\n\r\n", path, address);
+    stream.write_all(request.as_bytes()).unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    response
+}
+
+#[test]
+fn serves_drafts_by_default() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    let (mut child, address) = serve(root.path(), &[]);
+
+    let page = get(&address, "/posts/a.html");
+    let index = get(&address, "/");
+
+    // Changes show up without restarting.
+    write(root.path().join("content/posts/a.html"), "---\ntitle: Later\ndraft: true\n---\n").unwrap();
+    let changed = get(&address, "/");
+
+    let outside = get(&address, "/../mold.conf");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(page.starts_with("HTTP/1.1 200 OK\r\n"), "{}", page);
+    assert!(page.ends_with("[draft] Soon"), "{}", page);
+    assert!(index.ends_with("Staged "), "{}", index);
+    assert!(changed.ends_with("Later "), "{}", changed);
+    assert!(outside.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", outside);
+}
+
+#[test]
+fn leaves_out_drafts_when_asked() {
+    let root = Builder::new().prefix("mold").tempdir().unwrap();
+
+    setup(root.path());
+
+    let (mut child, address) = serve(root.path(), &["--no-drafts"]);
+
+    let page = get(&address, "/posts/a.html");
+    let index = get(&address, "/");
+
+    child.kill().unwrap();
+    child.wait().unwrap();
+
+    assert!(page.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", page);
+    assert!(index.starts_with("HTTP/1.1 200 OK\r\n"), "{}", index);
+    assert!(!index.contains("Staged"), "{}", index);
+}